@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::load_error::{LoadError, LoadErrorKind};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct LevelResult {
+    pub moves: u32,
+    pub cpu_steps: u64,
+}
+
+/// Personal bests keyed by `GamePlayState::level_hash` in hex. Moves and CPU steps are
+/// tracked separately, they can come from different runs.
+#[derive(Serialize, Deserialize, Default)]
+pub struct BestResults {
+    levels: BTreeMap<String, LevelResult>,
+}
+
+fn hash_key(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+impl BestResults {
+    /// `best.yaml` in user data dir.
+    pub fn default_path() -> Result<PathBuf, std::io::Error> {
+        crate::resource::get_user_dir().map(|dir| dir.join("best.yaml"))
+    }
+
+    /// Missing file gives empty results.
+    pub fn load(path: &Path) -> Result<BestResults, LoadError> {
+        if !path.exists() {
+            return Ok(BestResults::default());
+        }
+        let file = std::fs::File::open(path).map_err(|e| LoadError::io(path, e))?;
+        serde_yaml::from_reader(file)
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Config(e.to_string())))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), LoadError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| LoadError::io(dir, e))?;
+        }
+        let file = std::fs::File::create(path).map_err(|e| LoadError::io(path, e))?;
+        serde_yaml::to_writer(file, self)
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Config(e.to_string())))
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&LevelResult> {
+        self.levels.get(&hash_key(hash))
+    }
+
+    /// Keep the smaller of each counter, returns true if any of them improved.
+    pub fn record(&mut self, hash: u64, result: LevelResult) -> bool {
+        match self.levels.get_mut(&hash_key(hash)) {
+            Some(best) => {
+                let improved = result.moves < best.moves || result.cpu_steps < best.cpu_steps;
+                best.moves = std::cmp::min(best.moves, result.moves);
+                best.cpu_steps = std::cmp::min(best.cpu_steps, result.cpu_steps);
+                improved
+            }
+            None => {
+                self.levels.insert(hash_key(hash), result);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::TempPath;
+
+    #[test]
+    fn record() {
+        let mut best = BestResults::default();
+        let result = |moves, cpu_steps| LevelResult { moves, cpu_steps };
+        assert!(best.record(0x42, result(10, 20)));
+        assert!(!best.record(0x42, result(12, 25)));
+        assert!(best.record(0x42, result(15, 5)));
+        assert_eq!(best.get(0x42), Some(&result(10, 5)));
+        assert_eq!(best.get(0x43), None);
+
+        let path = TempPath::new("best.yaml");
+        assert!(BestResults::load(&path).unwrap().levels.is_empty());
+        best.save(&path).unwrap();
+        let loaded = BestResults::load(&path).unwrap();
+        assert_eq!(loaded.get(0x42), Some(&result(10, 5)));
+    }
+}
@@ -2,49 +2,268 @@ use std::fs::File;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::{Error, ErrorKind};
-use std::ops::{Index, IndexMut};
+use std::ops::{Index, IndexMut, Range};
 use std::path::Path;
 
 use crate::encoding::Encoding;
-use tgame::vecmath::V2;
+use crate::load_error::{LoadError, LoadErrorKind};
+use tgame::vecmath::{Rectangle, V2};
 
-const N: usize = 256;
+pub(crate) const N: usize = 256;
 
 pub type ByteGrid = Grid<u8>;
 
+/// Order of cells in raw binary pages.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum GridOrder {
+    /// Rows one after another, same as text pages.
+    RowMajor,
+    /// Columns one after another, same as indexing by `u16`.
+    ColumnMajor,
+}
+
+impl Default for GridOrder {
+    fn default() -> GridOrder {
+        GridOrder::RowMajor
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PageFormat {
+    Text,
+    Binary(GridOrder),
+    HexDump,
+}
+
+impl PageFormat {
+    /// Guess format from file extension: `.bin` - row major binary, `.hex` - hexdump, text otherwise.
+    pub fn from_path(path: &Path) -> PageFormat {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bin") => PageFormat::Binary(GridOrder::RowMajor),
+            Some("hex") => PageFormat::HexDump,
+            _ => PageFormat::Text,
+        }
+    }
+}
+
+const HEX_LINE_BYTES: usize = 16;
+
+/// 256x256 cells, stored row by row.
 #[derive(Clone)]
 pub struct Grid<T> {
-    data: Box<[[T; N]; N]>,
+    data: Box<[T]>,
 }
 
 impl<T: PartialEq> PartialEq for Grid<T> {
     fn eq(&self, other: &Grid<T>) -> bool {
-        self.data
-            .iter()
-            .zip(other.data.iter())
-            .all(|(a, b)| a.iter().eq(b.iter()))
+        self.data == other.data
     }
 }
 impl<T: Eq> Eq for Grid<T> {}
 
-impl Grid<u8> {
-    pub fn new() -> Grid<u8> {
+impl<T: Clone + Default> Default for Grid<T> {
+    fn default() -> Grid<T> {
+        Grid::new()
+    }
+}
+
+impl<T: Clone + Default> Grid<T> {
+    pub fn new() -> Grid<T> {
+        Grid::filled(T::default())
+    }
+}
+
+fn coordinates(i: usize) -> (u8, u8) {
+    ((i % N) as u8, (i / N) as u8)
+}
+
+/// Part of rectangle inside the grid as ranges of columns and rows.
+fn clip(rect: &Rectangle) -> (Range<usize>, Range<usize>) {
+    let range = |start: i32, len: i32| {
+        let end = std::cmp::min(start.saturating_add(len), N as i32);
+        let start = std::cmp::max(start, 0);
+        if start < end {
+            start as usize..end as usize
+        } else {
+            0..0
+        }
+    };
+    (
+        range(rect.pos.x, rect.size.x),
+        range(rect.pos.y, rect.size.y),
+    )
+}
+
+impl<T> Grid<T> {
+    pub fn filled(value: T) -> Grid<T>
+    where
+        T: Clone,
+    {
+        Grid {
+            data: vec![value; N * N].into_boxed_slice(),
+        }
+    }
+
+    /// Grid with each cell computed from its x and y.
+    pub fn from_fn<F: FnMut(u8, u8) -> T>(mut f: F) -> Grid<T> {
+        let data: Vec<T> = (0..N * N)
+            .map(|i| {
+                let (x, y) = coordinates(i);
+                f(x, y)
+            })
+            .collect();
+        Grid {
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        let data: Vec<U> = self.data.iter().map(f).collect();
         Grid {
-            data: Box::new([[0u8; N]; N]),
+            data: data.into_boxed_slice(),
         }
     }
 
-    pub fn load(path: &Path, encoding: &Encoding) -> Result<Grid<u8>, Error> {
+    /// Combine cells at the same position of two grids.
+    pub fn zip<U, V, F: FnMut(&T, &U) -> V>(&self, other: &Grid<U>, mut f: F) -> Grid<V> {
+        let data: Vec<V> = self
+            .data
+            .iter()
+            .zip(other.data.iter())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        Grid {
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    /// Cells with their x, y in row major order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = ((u8, u8), &'a T)> + 'a {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (coordinates(i), v))
+    }
+
+    pub fn iter_mut<'a>(&'a mut self) -> impl Iterator<Item = ((u8, u8), &'a mut T)> + 'a {
+        self.data
+            .iter_mut()
+            .enumerate()
+            .map(|(i, v)| (coordinates(i), v))
+    }
+
+    pub fn row(&self, y: u8) -> &[T] {
+        &self.data[y as usize * N..(y as usize + 1) * N]
+    }
+
+    pub fn row_mut(&mut self, y: u8) -> &mut [T] {
+        &mut self.data[y as usize * N..(y as usize + 1) * N]
+    }
+
+    /// Set all cells of rectangle, parts outside the grid are ignored.
+    pub fn fill_rect(&mut self, rect: &Rectangle, value: T)
+    where
+        T: Clone,
+    {
+        let (columns, rows) = clip(rect);
+        for y in rows {
+            for v in &mut self.data[y * N + columns.start..y * N + columns.end] {
+                *v = value.clone();
+            }
+        }
+    }
+
+    /// Copy `rect` of `src` so that its top left corner ends up at `dst`. Cells outside either
+    /// grid are skipped.
+    pub fn copy_rect(&mut self, src: &Grid<T>, rect: &Rectangle, dst: V2)
+    where
+        T: Clone,
+    {
+        let (columns, rows) = clip(rect);
+        let offset = dst - rect.pos;
+        for y in rows {
+            let ty = y as i32 + offset.y;
+            if ty < 0 || ty >= N as i32 {
+                continue;
+            }
+            for x in columns.clone() {
+                let tx = x as i32 + offset.x;
+                if tx >= 0 && tx < N as i32 {
+                    self.data[ty as usize * N + tx as usize] = src.data[y * N + x].clone();
+                }
+            }
+        }
+    }
+}
+
+impl Grid<u8> {
+    /// Load text page. Lines longer than 256 characters and lines after the 256th are ignored.
+    pub fn load(path: &Path, encoding: &Encoding) -> Result<Grid<u8>, LoadError> {
+        ByteGrid::load_text(path, encoding, false)
+    }
+
+    /// Same as `load` but overlong lines and files are rejected.
+    pub fn load_strict(path: &Path, encoding: &Encoding) -> Result<Grid<u8>, LoadError> {
+        ByteGrid::load_text(path, encoding, true)
+    }
+
+    pub fn load_text(
+        path: &Path,
+        encoding: &Encoding,
+        strict: bool,
+    ) -> Result<Grid<u8>, LoadError> {
         let mut result = ByteGrid::new();
-        let reader = BufReader::new(File::open(&path)?);
+        let reader = BufReader::new(File::open(&path).map_err(|e| LoadError::io(path, e))?);
+        let mut last_line = 0;
         for (i, line) in reader.lines().enumerate() {
-            let line = line?;
+            let line = line.map_err(|e| LoadError::io(path, e))?;
             let line = line.trim_end_matches('\r');
-            let (_, tail) = encoding.decode_utf8(line.chars(), &mut result.data[i])?;
+            if i >= N {
+                if !line.is_empty() {
+                    last_line = i + 1;
+                }
+                continue;
+            }
+            let (_, tail) = encoding
+                .decode_utf8(line.chars(), result.row_mut(i as u8))
+                .map_err(|e| {
+                    LoadError::new(
+                        path,
+                        LoadErrorKind::UnknownChar {
+                            line: i + 1,
+                            column: e.position + 1,
+                            c: e.c,
+                        },
+                    )
+                })?;
             if !tail.is_empty() {
-                eprintln!("Warning: trailing chars on line {}", i + 1);
+                if strict {
+                    return Err(LoadError::new(
+                        path,
+                        LoadErrorKind::LineTooLong {
+                            line: i + 1,
+                            length: line.chars().count(),
+                        },
+                    ));
+                }
+                eprintln!(
+                    "Warning: trailing chars on line {} of {}",
+                    i + 1,
+                    path.display()
+                );
             }
         }
+        if last_line > N {
+            if strict {
+                return Err(LoadError::new(path, LoadErrorKind::TooManyLines(last_line)));
+            }
+            eprintln!(
+                "Warning: {} has {} lines, lines after {} ignored",
+                path.display(),
+                last_line,
+                N
+            );
+        }
 
         Ok(result)
     }
@@ -58,10 +277,9 @@ impl Grid<u8> {
                 px = 0;
                 py += 1;
             } else {
-                result
-                    .data
-                    .get_mut(py)
-                    .map(|row| row.get_mut(px).map(|cell| *cell = *c));
+                if px < N && py < N {
+                    result[(px as u8, py as u8)] = *c;
+                }
                 px += 1;
             }
         }
@@ -70,7 +288,7 @@ impl Grid<u8> {
 
     pub fn save(&self, out: &mut ::std::io::Write, encoding: &Encoding) -> Result<(), Error> {
         let mut buf = [0u8; 4 * N + 8];
-        for line in self.data.iter() {
+        for line in self.data.chunks(N) {
             let mut offset = 0 as usize;
             for byte in line.iter() {
                 let c = encoding.byte_to_char[*byte as usize];
@@ -95,6 +313,207 @@ impl Grid<u8> {
         Ok(())
     }
 
+    pub fn load_format(
+        path: &Path,
+        format: PageFormat,
+        encoding: &Encoding,
+        strict: bool,
+    ) -> Result<Grid<u8>, LoadError> {
+        match format {
+            PageFormat::Text => ByteGrid::load_text(path, encoding, strict),
+            PageFormat::Binary(order) => ByteGrid::load_binary(path, order),
+            PageFormat::HexDump => ByteGrid::load_hexdump(path),
+        }
+    }
+
+    pub fn save_format(
+        &self,
+        out: &mut ::std::io::Write,
+        format: PageFormat,
+        encoding: &Encoding,
+    ) -> Result<(), Error> {
+        match format {
+            PageFormat::Text => self.save(out, encoding),
+            PageFormat::Binary(order) => out.write_all(&self.to_bytes(order)),
+            PageFormat::HexDump => self.save_hexdump(out, encoding),
+        }
+    }
+
+    pub fn from_bytes(data: &[u8], order: GridOrder) -> Option<Grid<u8>> {
+        if data.len() != N * N {
+            return None;
+        }
+        let mut result = ByteGrid::new();
+        match order {
+            GridOrder::RowMajor => {
+                result.data.copy_from_slice(data);
+            }
+            GridOrder::ColumnMajor => {
+                for (i, v) in data.iter().enumerate() {
+                    result[i as u16] = *v;
+                }
+            }
+        }
+        Some(result)
+    }
+
+    pub fn to_bytes(&self, order: GridOrder) -> Vec<u8> {
+        match order {
+            GridOrder::RowMajor => self.data.to_vec(),
+            GridOrder::ColumnMajor => (0u16..=std::u16::MAX).map(|i| self[i]).collect(),
+        }
+    }
+
+    pub fn load_binary(path: &Path, order: GridOrder) -> Result<Grid<u8>, LoadError> {
+        let data = std::fs::read(path).map_err(|e| LoadError::io(path, e))?;
+        ByteGrid::from_bytes(&data, order).ok_or_else(|| {
+            LoadError::new(
+                path,
+                LoadErrorKind::InvalidSize {
+                    expected: N * N,
+                    actual: data.len(),
+                },
+            )
+        })
+    }
+
+    /// Hexdump with 16 cells per line. Each line starts with row major offset `yyxx:` followed by
+    /// bytes in hex and glyphs between `|`. Lines starting with `#` are comments.
+    pub fn save_hexdump(
+        &self,
+        out: &mut ::std::io::Write,
+        encoding: &Encoding,
+    ) -> Result<(), Error> {
+        writeln!(
+            out,
+            "# bitflip hexdump: yyxx: {} bytes |glyphs|",
+            HEX_LINE_BYTES
+        )?;
+        let mut line = String::new();
+        for (y, row) in self.data.chunks(N).enumerate() {
+            for (chunk_id, chunk) in row.chunks(HEX_LINE_BYTES).enumerate() {
+                line.clear();
+                line.push_str(&format!("{:02x}{:02x}:", y, chunk_id * HEX_LINE_BYTES));
+                for byte in chunk {
+                    line.push_str(&format!(" {:02x}", byte));
+                }
+                line.push_str("  |");
+                line.extend(chunk.iter().map(|b| encoding.byte_to_char[*b as usize]));
+                line.push_str("|\n");
+                out.write_all(line.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read hexdump written by `save_hexdump`. Lines may be missing or reordered, missing cells are 0.
+    pub fn load_hexdump(path: &Path) -> Result<Grid<u8>, LoadError> {
+        let mut result = ByteGrid::new();
+        let reader = BufReader::new(File::open(&path).map_err(|e| LoadError::io(path, e))?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(|e| LoadError::io(path, e))?;
+            let line = line.trim_end_matches('\r');
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let bad_hex = |column: usize| {
+                LoadError::new(
+                    path,
+                    LoadErrorKind::InvalidHex {
+                        line: i + 1,
+                        column: column + 1,
+                    },
+                )
+            };
+            let colon = line
+                .find(':')
+                .ok_or_else(|| bad_hex(line.chars().count()))?;
+            let address = line[..colon].trim();
+            if address.is_empty() {
+                return Err(bad_hex(0));
+            }
+            let mut offset = usize::from_str_radix(address, 16)
+                .map_err(|_| bad_hex(line[..colon].chars().count()))?;
+            let mut column = line[..=colon].chars().count();
+            let mut chars = line[colon + 1..].chars().peekable();
+            loop {
+                while chars.peek().map_or(false, |c| c.is_whitespace()) {
+                    chars.next();
+                    column += 1;
+                }
+                match chars.peek() {
+                    None | Some('|') => break,
+                    _ => {}
+                }
+                let high = chars.next().and_then(|c| c.to_digit(16));
+                let low = chars.next().and_then(|c| c.to_digit(16));
+                let separated = chars
+                    .peek()
+                    .map_or(true, |c| c.is_whitespace() || *c == '|');
+                match (high, low) {
+                    (Some(high), Some(low)) if separated && offset < N * N => {
+                        result.data[offset] = (high * 16 + low) as u8;
+                    }
+                    _ => return Err(bad_hex(column)),
+                }
+                offset += 1;
+                column += 2;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Single bit of each cell in row major order.
+    pub fn plane_bits(&self, plane: u8) -> Vec<bool> {
+        let mask = 1u8 << (plane & 7);
+        self.data.iter().map(|v| v & mask != 0).collect()
+    }
+
+    /// Replace single bit of each cell, other bits are kept.
+    pub fn set_plane_bits(&mut self, plane: u8, bits: &[bool]) {
+        let mask = 1u8 << (plane & 7);
+        for (cell, bit) in self.data.iter_mut().zip(bits) {
+            if *bit {
+                *cell |= mask;
+            } else {
+                *cell &= !mask;
+            }
+        }
+    }
+
+    /// Rows of cells inside `rect`, parts outside the grid are left out.
+    pub fn extract(&self, rect: &Rectangle) -> Vec<Vec<u8>> {
+        let (columns, rows) = clip(rect);
+        rows.map(|y| self.row(y as u8)[columns.clone()].to_vec())
+            .collect()
+    }
+
+    /// Write rows of cells with top left corner at `pos`. Only bits set in `mask` are changed,
+    /// cells outside the grid are skipped.
+    pub fn blit(&mut self, rows: &[Vec<u8>], pos: V2, mask: u8) {
+        for (dy, row) in rows.iter().enumerate() {
+            let y = pos.y + dy as i32;
+            if y < 0 || y >= N as i32 {
+                continue;
+            }
+            for (dx, v) in row.iter().enumerate() {
+                let x = pos.x + dx as i32;
+                if x >= 0 && x < N as i32 {
+                    let cell = &mut self[(x as u8, y as u8)];
+                    *cell = (*cell & !mask) | (v & mask);
+                }
+            }
+        }
+    }
+
+    /// FNV-1a hash of all cells in row major order.
+    pub fn checksum(&self) -> u32 {
+        self.data.iter().fold(0x811c_9dc5u32, |hash, byte| {
+            (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+        })
+    }
+
     pub fn diff(&self, after: &Grid<u8>) -> ByteGridDiff {
         let mut result = ByteGridDiff::new();
         for i in 0u16..=::std::u16::MAX {
@@ -111,6 +530,7 @@ impl Grid<u8> {
                                 add_new_hunk = false;
                             }
                         }
+                        _ => {}
                     }
                 }
                 if add_new_hunk {
@@ -118,9 +538,46 @@ impl Grid<u8> {
                 }
             }
         }
+        result.compact();
+        result
+    }
+
+    /// Same as `diff` but records old values so that the diff can be checked and reversed.
+    pub fn diff_reversible(&self, after: &Grid<u8>) -> ByteGridDiff {
+        let mut result = self.diff(after);
+        let old = result.changes().map(|(pos, _)| self[pos]).collect();
+        result.old = Some(old);
         result
     }
 
+    /// Three-way merge of two edited versions of `base`. Cells changed only on one side or
+    /// changed to the same value are merged, other changed cells keep value from `ours` and are
+    /// reported as conflicts.
+    pub fn merge(
+        base: &Grid<u8>,
+        ours: &Grid<u8>,
+        theirs: &Grid<u8>,
+    ) -> (Grid<u8>, Vec<MergeConflict>) {
+        let mut result = ours.clone();
+        let mut conflicts = Vec::new();
+        for (pos, value) in base.diff(theirs).changes() {
+            if value == base[pos] || value == ours[pos] {
+                continue;
+            }
+            if ours[pos] == base[pos] {
+                result[pos] = value;
+            } else {
+                conflicts.push(MergeConflict {
+                    pos,
+                    base: base[pos],
+                    ours: ours[pos],
+                    theirs: value,
+                });
+            }
+        }
+        (result, conflicts)
+    }
+
     pub fn patch(&mut self, diff: &ByteGridDiff) {
         for hunk in &diff.hunks {
             match hunk {
@@ -130,31 +587,344 @@ impl Grid<u8> {
                         self[(pos + idx as u16)] = *v;
                     }
                 }
+                DiffHunk::Fill(pos, len, value) => {
+                    let l = std::cmp::min(*len, std::u16::MAX as usize + 1 - *pos as usize);
+                    for idx in 0..l {
+                        self[pos + idx as u16] = *value;
+                    }
+                }
+                DiffHunk::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    value,
+                } => {
+                    let rect = Rectangle {
+                        pos: V2::make(*x as i32, *y as i32),
+                        size: V2::make(*width as i32, *height as i32),
+                    };
+                    self.fill_rect(&rect, *value);
+                }
             }
         }
     }
 }
 
+/// Cell changed differently by both sides of merge.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct MergeConflict {
+    pub pos: u16,
+    pub base: u8,
+    pub ours: u8,
+    pub theirs: u8,
+}
+
+impl MergeConflict {
+    pub const MARK: u8 = 0xdb;
+
+    pub fn x(&self) -> u8 {
+        (self.pos >> 8) as u8
+    }
+
+    pub fn y(&self) -> u8 {
+        self.pos as u8
+    }
+
+    /// Page with conflicting cells set to `MARK` and everything else 0.
+    pub fn overlay(conflicts: &[MergeConflict]) -> Grid<u8> {
+        let mut result = ByteGrid::new();
+        for conflict in conflicts {
+            result[conflict.pos] = MergeConflict::MARK;
+        }
+        result
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 enum DiffHunk {
     Seq(u16, Vec<u8>),
+    /// Run of cells in `u16` index order set to the same value.
+    Fill(u16, usize, u8),
+    /// Rectangle of cells set to the same value.
+    Rect {
+        x: u8,
+        y: u8,
+        width: usize,
+        height: usize,
+        value: u8,
+    },
 }
 
+/// Same as `u16` indexing of grid.
+fn cell_index(x: u8, y: u8) -> u16 {
+    (x as u16) << 8 | y as u16
+}
+
+const TAG_SEQ: u8 = 0;
+const TAG_FILL: u8 = 1;
+const TAG_RECT: u8 = 2;
+/// Size of tagged `Seq` record without data.
+const SEQ_HEADER_SIZE: usize = 4;
+/// Size of tagged `Fill` and `Rect` records.
+const FILL_SIZE: usize = 6;
+
 pub struct ByteGridDiff {
     hunks: Vec<DiffHunk>,
+    /// Values of cells before the patch in `changes` order, `None` when not recorded.
+    old: Option<Vec<u8>>,
+}
+
+/// Cell which doesn't have the value diff was made against.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CellConflict {
+    pub pos: u16,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Result of comparing single hunk with the page it is applied to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct HunkCheck {
+    /// First cell of the hunk.
+    pub pos: u16,
+    pub cells: usize,
+    pub conflicts: Vec<CellConflict>,
+}
+
+fn hunk_changes<'a>(hunk: &'a DiffHunk) -> Box<Iterator<Item = (u16, u8)> + 'a> {
+    match hunk {
+        DiffHunk::Seq(pos, data) => Box::new(
+            data.iter()
+                .take(std::u16::MAX as usize + 1 - *pos as usize)
+                .enumerate()
+                .map(move |(i, v)| (pos + i as u16, *v)),
+        ),
+        DiffHunk::Fill(pos, len, value) => {
+            let (pos, value) = (*pos, *value);
+            let len = std::cmp::min(*len, std::u16::MAX as usize + 1 - pos as usize);
+            Box::new((0..len).map(move |i| (pos + i as u16, value)))
+        }
+        DiffHunk::Rect {
+            x,
+            y,
+            width,
+            height,
+            value,
+        } => {
+            let (x, y, value) = (*x as usize, *y as usize, *value);
+            let width = std::cmp::min(*width, N - x);
+            let height = std::cmp::min(*height, N - y);
+            Box::new((x..x + width).flat_map(move |x| {
+                (y..y + height).map(move |y| (cell_index(x as u8, y as u8), value))
+            }))
+        }
+    }
 }
 
 impl ByteGridDiff {
     fn new() -> ByteGridDiff {
-        ByteGridDiff { hunks: Vec::new() }
+        ByteGridDiff {
+            hunks: Vec::new(),
+            old: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hunks.is_empty()
+    }
+
+    /// Cells written by the patch in hunk order. Includes unchanged cells between nearby
+    /// changes.
+    pub fn changes<'a>(&'a self) -> impl Iterator<Item = (u16, u8)> + 'a {
+        self.hunks.iter().flat_map(hunk_changes)
+    }
+
+    pub fn old_values(&self) -> Option<&[u8]> {
+        self.old.as_ref().map(|old| old.as_slice())
+    }
+
+    /// Set values cells had before the patch, one for each item of `changes`. Returns false
+    /// if the count doesn't match.
+    pub fn set_old_values(&mut self, old: Vec<u8>) -> bool {
+        if old.len() != self.changes().count() {
+            return false;
+        }
+        self.old = Some(old);
+        true
     }
 
+    /// Diff undoing this one, `None` if old values weren't recorded.
+    pub fn reverse(&self) -> Option<ByteGridDiff> {
+        let old = self.old.as_ref()?;
+        let mut before = ByteGrid::new();
+        let mut after = ByteGrid::new();
+        for ((pos, value), old) in self.changes().zip(old) {
+            before[pos] = *old;
+            after[pos] = value;
+        }
+        Some(after.diff_reversible(&before))
+    }
+
+    /// Compare old values of each hunk with `base`, `None` if old values weren't recorded.
+    pub fn check(&self, base: &ByteGrid) -> Option<Vec<HunkCheck>> {
+        let mut old = self.old.as_ref()?.iter();
+        let mut result = Vec::new();
+        for hunk in &self.hunks {
+            let mut check = HunkCheck {
+                pos: hunk_changes(hunk).next().map_or(0, |(pos, _)| pos),
+                cells: 0,
+                conflicts: Vec::new(),
+            };
+            for ((pos, _), expected) in hunk_changes(hunk).zip(&mut old) {
+                check.cells += 1;
+                if base[pos] != *expected {
+                    check.conflicts.push(CellConflict {
+                        pos,
+                        expected: *expected,
+                        actual: base[pos],
+                    });
+                }
+            }
+            result.push(check);
+        }
+        Some(result)
+    }
+
+    /// Replace long runs of equal bytes with `Fill` and fills of neighbouring columns with
+    /// `Rect` hunks where that makes the compact encoding smaller.
+    fn compact(&mut self) {
+        let mut hunks = Vec::new();
+        for hunk in self.hunks.drain(..) {
+            let (pos, data) = match hunk {
+                DiffHunk::Seq(pos, data) => (pos as usize, data),
+                hunk => {
+                    hunks.push(hunk);
+                    continue;
+                }
+            };
+            let mut seq_start = 0;
+            let mut run_start = 0;
+            for i in 1..=data.len() {
+                if i < data.len() && data[i] == data[run_start] {
+                    continue;
+                }
+                let len = i - run_start;
+                // fill in the middle splits sequence and costs an extra header, fill replacing
+                // whole sequence saves one
+                let cost = match (run_start == 0, i == data.len()) {
+                    (true, true) => FILL_SIZE - SEQ_HEADER_SIZE,
+                    (false, false) => FILL_SIZE + SEQ_HEADER_SIZE,
+                    _ => FILL_SIZE,
+                };
+                if len > cost {
+                    if seq_start < run_start {
+                        let seq = data[seq_start..run_start].to_vec();
+                        hunks.push(DiffHunk::Seq((pos + seq_start) as u16, seq));
+                    }
+                    hunks.push(DiffHunk::Fill(
+                        (pos + run_start) as u16,
+                        len,
+                        data[run_start],
+                    ));
+                    seq_start = i;
+                }
+                run_start = i;
+            }
+            if seq_start < data.len() {
+                let seq = data[seq_start..].to_vec();
+                hunks.push(DiffHunk::Seq((pos + seq_start) as u16, seq));
+            }
+        }
+
+        // single column fills by (y, height, value) -> (hunk index, last column)
+        let mut columns = std::collections::HashMap::new();
+        for hunk in hunks {
+            if let DiffHunk::Fill(pos, len, value) = hunk {
+                let (x, y) = ((pos >> 8) as u8, pos as u8);
+                if y as usize + len <= N {
+                    let key = (y, len, value);
+                    if let Some((idx, last_x)) = columns.get(&key).cloned() {
+                        if last_x as usize + 1 == x as usize {
+                            match &mut self.hunks[idx] {
+                                DiffHunk::Rect { width, .. } => *width += 1,
+                                hunk => {
+                                    *hunk = DiffHunk::Rect {
+                                        x: last_x,
+                                        y,
+                                        width: 2,
+                                        height: len,
+                                        value,
+                                    }
+                                }
+                            }
+                            columns.insert(key, (idx, x));
+                            continue;
+                        }
+                    }
+                    columns.insert(key, (self.hunks.len(), x));
+                }
+            }
+            self.hunks.push(hunk);
+        }
+    }
+
+    /// Hunks as sequences of bytes, contiguous ones joined together.
+    fn sequences(&self) -> Vec<(u16, Vec<u8>)> {
+        let mut result: Vec<(u16, Vec<u8>)> = Vec::new();
+        for hunk in &self.hunks {
+            let parts = match hunk {
+                DiffHunk::Seq(pos, data) => vec![(*pos, data.clone())],
+                DiffHunk::Fill(pos, len, value) => vec![(*pos, vec![*value; *len])],
+                DiffHunk::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    value,
+                } => (*x as usize..*x as usize + *width)
+                    .map(|x| (cell_index(x as u8, *y), vec![*value; *height]))
+                    .collect(),
+            };
+            for (pos, data) in parts {
+                if let Some((last_pos, last)) = result.last_mut() {
+                    if *last_pos as usize + last.len() == pos as usize {
+                        last.extend_from_slice(&data);
+                        continue;
+                    }
+                }
+                result.push((pos, data));
+            }
+        }
+        result
+    }
+
+    /// Legacy format made only of byte sequence records: position u16, length - 1 and data.
     pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        for (pos, data) in self.sequences() {
+            let mut current_pos = pos as usize;
+            for fragment in data.chunks(256) {
+                result.push(current_pos as u8);
+                result.push((current_pos >> 8) as u8);
+                result.push((fragment.len() - 1) as u8);
+                result.extend_from_slice(fragment);
+                current_pos += fragment.len();
+            }
+        }
+        result
+    }
+
+    /// Tagged records. Sequence: 0, position u16, length - 1, data. Fill: 1, position u16,
+    /// length - 1 as u16, value. Rectangle: 2, x, y, width - 1, height - 1, value.
+    pub fn serialize_compact(&self) -> Vec<u8> {
         let mut result = Vec::new();
         for hunk in &self.hunks {
             match hunk {
                 DiffHunk::Seq(pos, data) => {
                     let mut current_pos = *pos as usize;
                     for fragment in data.chunks(256) {
+                        result.push(TAG_SEQ);
                         result.push(current_pos as u8);
                         result.push((current_pos >> 8) as u8);
                         result.push((fragment.len() - 1) as u8);
@@ -162,73 +932,143 @@ impl ByteGridDiff {
                         current_pos += fragment.len();
                     }
                 }
+                DiffHunk::Fill(pos, len, value) => {
+                    let len = len - 1;
+                    result.extend_from_slice(&[
+                        TAG_FILL,
+                        *pos as u8,
+                        (pos >> 8) as u8,
+                        len as u8,
+                        (len >> 8) as u8,
+                        *value,
+                    ]);
+                }
+                DiffHunk::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    value,
+                } => {
+                    result.extend_from_slice(&[
+                        TAG_RECT,
+                        *x,
+                        *y,
+                        (width - 1) as u8,
+                        (height - 1) as u8,
+                        *value,
+                    ]);
+                }
             }
         }
         result
     }
 
-    pub fn deserialize(data: &Vec<u8>) -> Result<ByteGridDiff, ()> {
+    /// Parse tagged records written by `serialize_compact`. On failure returns offset of the
+    /// broken record.
+    pub fn deserialize_compact(data: &[u8]) -> Result<ByteGridDiff, usize> {
         let mut result = ByteGridDiff::new();
         let mut pos = 0 as usize;
-        while data.len() - pos >= 4 {
-            let grid_pos = data[pos] as u16 + ((data[pos + 1] as u16) << 8);
-            let len = (data[pos + 2] as usize) + 1;
-            pos += 3;
-            if let Some(hunk_data) = data.get(pos..pos + len) {
-                result.hunks.push(DiffHunk::Seq(grid_pos, hunk_data.into()));
-            } else {
-                return Err(());
-            }
-            pos += len;
+        while pos < data.len() {
+            let size = match data[pos] {
+                TAG_SEQ => {
+                    let header = data.get(pos..pos + SEQ_HEADER_SIZE).ok_or(pos)?;
+                    let grid_pos = header[1] as u16 + ((header[2] as u16) << 8);
+                    let len = (header[3] as usize) + 1;
+                    let hunk_data = data
+                        .get(pos + SEQ_HEADER_SIZE..pos + SEQ_HEADER_SIZE + len)
+                        .ok_or(pos)?;
+                    result.hunks.push(DiffHunk::Seq(grid_pos, hunk_data.into()));
+                    SEQ_HEADER_SIZE + len
+                }
+                TAG_FILL => {
+                    let record = data.get(pos..pos + FILL_SIZE).ok_or(pos)?;
+                    let grid_pos = record[1] as u16 + ((record[2] as u16) << 8);
+                    let len = record[3] as usize + ((record[4] as usize) << 8) + 1;
+                    if grid_pos as usize + len > N * N {
+                        return Err(pos);
+                    }
+                    result.hunks.push(DiffHunk::Fill(grid_pos, len, record[5]));
+                    FILL_SIZE
+                }
+                TAG_RECT => {
+                    let record = data.get(pos..pos + FILL_SIZE).ok_or(pos)?;
+                    let (width, height) = (record[3] as usize + 1, record[4] as usize + 1);
+                    if record[1] as usize + width > N || record[2] as usize + height > N {
+                        return Err(pos);
+                    }
+                    result.hunks.push(DiffHunk::Rect {
+                        x: record[1],
+                        y: record[2],
+                        width,
+                        height,
+                        value: record[5],
+                    });
+                    FILL_SIZE
+                }
+                _ => return Err(pos),
+            };
+            pos += size;
         }
-        if data.len() - pos > 0 {
-            Err(())
-        } else {
-            Ok(result)
+        Ok(result)
+    }
+
+    /// Parse sequence of hunk records. On failure returns offset of the broken record.
+    pub fn deserialize(data: &[u8]) -> Result<ByteGridDiff, usize> {
+        let mut result = ByteGridDiff::new();
+        let mut pos = 0 as usize;
+        while pos < data.len() {
+            let header = data.get(pos..pos + 3).ok_or(pos)?;
+            let grid_pos = header[0] as u16 + ((header[1] as u16) << 8);
+            let len = (header[2] as usize) + 1;
+            let hunk_data = data.get(pos + 3..pos + 3 + len).ok_or(pos)?;
+            result.hunks.push(DiffHunk::Seq(grid_pos, hunk_data.into()));
+            pos += 3 + len;
         }
+        Ok(result)
     }
 }
 
-impl Index<(u8, u8)> for ByteGrid {
-    type Output = u8;
+impl<T> Index<(u8, u8)> for Grid<T> {
+    type Output = T;
 
-    fn index(&self, idx: (u8, u8)) -> &u8 {
-        &self.data[idx.1 as usize][idx.0 as usize]
+    fn index(&self, idx: (u8, u8)) -> &T {
+        &self.data[idx.1 as usize * N + idx.0 as usize]
     }
 }
 
-impl IndexMut<(u8, u8)> for ByteGrid {
-    fn index_mut(&mut self, idx: (u8, u8)) -> &mut u8 {
-        &mut self.data[idx.1 as usize][idx.0 as usize]
+impl<T> IndexMut<(u8, u8)> for Grid<T> {
+    fn index_mut(&mut self, idx: (u8, u8)) -> &mut T {
+        &mut self.data[idx.1 as usize * N + idx.0 as usize]
     }
 }
 
-impl Index<u16> for ByteGrid {
-    type Output = u8;
-    fn index(&self, idx: u16) -> &u8 {
+impl<T> Index<u16> for Grid<T> {
+    type Output = T;
+    fn index(&self, idx: u16) -> &T {
         let x = (idx >> 8) as u8;
         let y = (idx & 0xff) as u8;
         &self[(x, y)]
     }
 }
 
-impl IndexMut<u16> for ByteGrid {
-    fn index_mut(&mut self, idx: u16) -> &mut u8 {
+impl<T> IndexMut<u16> for Grid<T> {
+    fn index_mut(&mut self, idx: u16) -> &mut T {
         let x = (idx >> 8) as u8;
         let y = (idx & 0xff) as u8;
         &mut self[(x, y)]
     }
 }
 
-impl Index<V2> for ByteGrid {
-    type Output = u8;
-    fn index(&self, idx: V2) -> &u8 {
+impl<T> Index<V2> for Grid<T> {
+    type Output = T;
+    fn index(&self, idx: V2) -> &T {
         &self[(idx.x as u8, idx.y as u8)]
     }
 }
 
-impl IndexMut<V2> for ByteGrid {
-    fn index_mut(&mut self, idx: V2) -> &mut u8 {
+impl<T> IndexMut<V2> for Grid<T> {
+    fn index_mut(&mut self, idx: V2) -> &mut T {
         &mut self[(idx.x as u8, idx.y as u8)]
     }
 }
@@ -272,11 +1112,95 @@ impl Bits256 {
             *v = 0;
         }
     }
+
+    /// Indices of set bits in increasing order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = u8> + 'a {
+        (0..Bits256::BIT_COUNT)
+            .map(|i| i as u8)
+            .filter(move |i| self.get(*i))
+    }
+
+    pub fn count(&self) -> usize {
+        self.data.iter().map(|v| v.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().all(|v| *v == 0)
+    }
+
+    fn combine<F: Fn(WordType, WordType) -> WordType>(&self, other: &Bits256, f: F) -> Bits256 {
+        let mut result = Bits256::new();
+        for (i, v) in result.data.iter_mut().enumerate() {
+            *v = f(self.data[i], other.data[i]);
+        }
+        result
+    }
+
+    pub fn union(&self, other: &Bits256) -> Bits256 {
+        self.combine(other, |a, b| a | b)
+    }
+
+    pub fn intersection(&self, other: &Bits256) -> Bits256 {
+        self.combine(other, |a, b| a & b)
+    }
+
+    pub fn difference(&self, other: &Bits256) -> Bits256 {
+        self.combine(other, |a, b| a & !b)
+    }
+}
+
+impl std::iter::FromIterator<u8> for Bits256 {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Bits256 {
+        let mut result = Bits256::new();
+        for i in iter {
+            result.set(i, true);
+        }
+        result
+    }
+}
+
+/// Set bits as hex ranges, for example `00-03,42`.
+impl std::fmt::Display for Bits256 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut first = true;
+        let mut bits = self.iter().peekable();
+        while let Some(start) = bits.next() {
+            let mut end = start;
+            while bits.peek() == Some(&end.wrapping_add(1)) && end != 255 {
+                end = bits.next().unwrap();
+            }
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            if start == end {
+                write!(f, "{:02x}", start)?;
+            } else {
+                write!(f, "{:02x}-{:02x}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stored as list of set bits.
+impl serde::Serialize for Bits256 {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Bits256 {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Bits256, D::Error> {
+        let bits: Vec<u8> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(bits.into_iter().collect())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::resource::TempPath;
 
     fn get_test_data() -> Vec<(ByteGrid, ByteGrid)> {
         let mut ans = Vec::new();
@@ -335,6 +1259,197 @@ mod tests {
         }
     }
 
+    #[test]
+    fn compact_hunks() {
+        let before = ByteGrid::new();
+        let mut after = ByteGrid::new();
+        for x in 10..20 {
+            for y in 5..9 {
+                after[(x, y)] = b'#';
+            }
+        }
+        for y in 0..=255 {
+            after[(30, y)] = b'|';
+        }
+        after[(40, 3)] = 1;
+        after[(40, 4)] = 2;
+        let diff = before.diff(&after);
+        assert_eq!(
+            diff.hunks,
+            vec![
+                DiffHunk::Rect {
+                    x: 10,
+                    y: 5,
+                    width: 10,
+                    height: 4,
+                    value: b'#'
+                },
+                DiffHunk::Fill(cell_index(30, 0), 256, b'|'),
+                DiffHunk::Seq(cell_index(40, 3), vec![1, 2]),
+            ]
+        );
+
+        let compact = diff.serialize_compact();
+        assert_eq!(compact.len(), 2 * FILL_SIZE + SEQ_HEADER_SIZE + 2);
+        let legacy = diff.serialize();
+        assert_eq!(legacy.len(), 10 * (3 + 4) + 3 + 256 + 3 + 2);
+        for data in &[
+            ByteGridDiff::deserialize_compact(&compact).unwrap(),
+            ByteGridDiff::deserialize(&legacy).unwrap(),
+        ] {
+            let mut patched = before.clone();
+            patched.patch(data);
+            assert!(patched == after);
+        }
+
+        let mut cleared = after.clone();
+        cleared.patch(&after.diff(&before));
+        assert!(cleared == before);
+        assert_eq!(after.diff(&before).changes().count(), 10 * 4 + 256 + 2);
+    }
+
+    #[test]
+    fn compact_diff_patch() {
+        for (a, b) in get_test_data() {
+            let serialized = a.diff(&b).serialize_compact();
+            let diff = ByteGridDiff::deserialize_compact(&serialized).unwrap();
+            let mut c = a.clone();
+            c.patch(&diff);
+            assert!(c == b);
+        }
+        let mut full = ByteGrid::new();
+        full.patch(&ByteGridDiff {
+            hunks: vec![DiffHunk::Fill(0, N * N, 7)],
+            old: None,
+        });
+        let diff = ByteGrid::new().diff(&full);
+        assert_eq!(diff.hunks, vec![DiffHunk::Fill(0, N * N, 7)]);
+        assert_eq!(diff.serialize_compact().len(), FILL_SIZE);
+
+        // unknown tag, fill past the end, rectangle past the edge
+        assert_eq!(ByteGridDiff::deserialize_compact(&[3]).err(), Some(0));
+        assert_eq!(
+            ByteGridDiff::deserialize_compact(&[1, 1, 0, 0xff, 0xff, 0]).err(),
+            Some(0)
+        );
+        assert_eq!(
+            ByteGridDiff::deserialize_compact(&[0, 0, 0, 0, 5, 2, 250, 0, 9, 0, 1]).err(),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn reversible_diff() {
+        for (a, b) in get_test_data() {
+            let diff = a.diff_reversible(&b);
+            assert!(diff
+                .check(&a)
+                .unwrap()
+                .iter()
+                .all(|h| h.conflicts.is_empty()));
+            let mut c = a.clone();
+            c.patch(&diff);
+            assert!(c == b);
+            c.patch(&diff.reverse().unwrap());
+            assert!(c == a);
+        }
+        assert!(ByteGrid::new()
+            .diff(&get_test_data()[0].1)
+            .reverse()
+            .is_none());
+
+        let before = ByteGrid::from_raw_str(b"....\n....");
+        let after = ByteGrid::from_raw_str(b"....\n.AAA");
+        let mut changed = before.clone();
+        changed[(2, 1)] = b'x';
+        let diff = before.diff_reversible(&after);
+        let hunks = diff.check(&changed).unwrap();
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].pos, cell_index(1, 1));
+        assert!(hunks[0].conflicts.is_empty());
+        assert_eq!(
+            hunks[1].conflicts,
+            vec![CellConflict {
+                pos: cell_index(2, 1),
+                expected: b'.',
+                actual: b'x'
+            }]
+        );
+
+        let mut diff = before.diff(&after);
+        assert!(!diff.set_old_values(vec![b'.'; 2]));
+        assert!(diff.set_old_values(vec![b'.'; 3]));
+        assert_eq!(diff.old_values(), Some(&[b'.'; 3][..]));
+    }
+
+    #[test]
+    fn diff_deserialize_errors() {
+        assert!(ByteGridDiff::deserialize(&[]).unwrap().is_empty());
+        assert_eq!(
+            ByteGridDiff::deserialize(&[1, 0, 0, 5])
+                .unwrap()
+                .hunks
+                .len(),
+            1
+        );
+        // truncated header and data
+        assert_eq!(
+            ByteGridDiff::deserialize(&[1, 0, 0, 5, 1, 0]).err(),
+            Some(4)
+        );
+        assert_eq!(ByteGridDiff::deserialize(&[1, 0, 1, 5]).err(), Some(0));
+        assert_eq!(ByteGridDiff::deserialize(&[1, 0, 0, 5, 1]).err(), Some(4));
+    }
+
+    #[test]
+    fn checksum() {
+        let a = ByteGrid::new();
+        let mut b = ByteGrid::new();
+        assert_eq!(a.checksum(), b.checksum());
+        b[(3, 4)] = 1;
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn merge() {
+        let mut base = ByteGrid::new();
+        base[(1, 1)] = 1;
+        base[(2, 2)] = 2;
+        base[(3, 3)] = 3;
+        let mut ours = base.clone();
+        ours[(1, 1)] = 10; // only ours
+        ours[(3, 3)] = 30; // conflict
+        ours[(4, 4)] = 40; // same change on both sides
+        let mut theirs = base.clone();
+        theirs[(2, 2)] = 20; // only theirs
+        theirs[(3, 3)] = 31;
+        theirs[(4, 4)] = 40;
+        theirs[(255, 255)] = 50;
+
+        let (merged, conflicts) = ByteGrid::merge(&base, &ours, &theirs);
+        assert_eq!(merged[(1, 1)], 10);
+        assert_eq!(merged[(2, 2)], 20);
+        assert_eq!(merged[(3, 3)], 30);
+        assert_eq!(merged[(4, 4)], 40);
+        assert_eq!(merged[(255, 255)], 50);
+        assert_eq!(
+            conflicts,
+            vec![MergeConflict {
+                pos: 0x0303,
+                base: 3,
+                ours: 30,
+                theirs: 31
+            }]
+        );
+        let overlay = MergeConflict::overlay(&conflicts);
+        assert_eq!(overlay[(3, 3)], MergeConflict::MARK);
+        assert_eq!(overlay[(4, 4)], 0);
+
+        let (merged, conflicts) = ByteGrid::merge(&base, &base, &theirs);
+        assert!(merged == theirs);
+        assert!(conflicts.is_empty());
+    }
+
     #[test]
     fn from_str() {
         let test_data = ByteGrid::from_raw_str(b"aa\nbbb");
@@ -347,6 +1462,174 @@ mod tests {
         debug_assert_eq!(test_data[(0, 2)], 0u8);
     }
 
+    fn write_temp_page(name: &str, content: &str) -> TempPath {
+        let path = TempPath::new(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_errors() {
+        let encoding = Encoding::get_encoding("437").unwrap();
+
+        let path = write_temp_page("unknown_char.txt", "abc\nde\u{0101}f\n");
+        let err = ByteGrid::load(&path, &encoding).err().unwrap();
+        assert_eq!(err.path, *path);
+        assert_eq!(err.line(), Some(2));
+        assert_eq!(err.column(), Some(3));
+        match err.kind {
+            LoadErrorKind::UnknownChar { c, .. } => assert_eq!(c, '\u{0101}'),
+            _ => panic!("unexpected error {}", err),
+        }
+
+        let long_line = format!("{}\n", "a".repeat(300));
+        let path = write_temp_page("long_line.txt", &long_line);
+        let grid = ByteGrid::load(&path, &encoding).unwrap();
+        assert_eq!(grid[(255, 0)], b'a');
+        let err = ByteGrid::load_strict(&path, &encoding).err().unwrap();
+        assert_eq!(err.line(), Some(1));
+
+        let many_lines = "x\n".repeat(300);
+        let path = write_temp_page("many_lines.txt", &many_lines);
+        let grid = ByteGrid::load(&path, &encoding).unwrap();
+        assert_eq!(grid[(0, 255)], b'x');
+        match ByteGrid::load_strict(&path, &encoding).err().unwrap().kind {
+            LoadErrorKind::TooManyLines(n) => assert_eq!(n, 300),
+            _ => panic!("expected too many lines"),
+        }
+
+        let trailing_empty = format!("{}\n\n", "x\n".repeat(256));
+        let path = write_temp_page("trailing_empty.txt", &trailing_empty);
+        assert!(ByteGrid::load_strict(&path, &encoding).is_ok());
+    }
+
+    #[test]
+    fn binary_formats() {
+        let mut grid = ByteGrid::new();
+        grid[(1, 0)] = 1;
+        grid[(0, 1)] = 2;
+        grid[(255, 255)] = 3;
+
+        let row_major = grid.to_bytes(GridOrder::RowMajor);
+        assert_eq!(row_major.len(), 65536);
+        assert_eq!(&row_major[..2], &[0, 1]);
+        assert_eq!(row_major[256], 2);
+        let column_major = grid.to_bytes(GridOrder::ColumnMajor);
+        assert_eq!(&column_major[..2], &[0, 2]);
+        assert_eq!(column_major[256], 1);
+
+        for order in vec![GridOrder::RowMajor, GridOrder::ColumnMajor] {
+            let bytes = grid.to_bytes(order);
+            assert!(ByteGrid::from_bytes(&bytes, order).unwrap() == grid);
+        }
+        assert!(ByteGrid::from_bytes(&row_major[1..], GridOrder::RowMajor).is_none());
+    }
+
+    #[test]
+    fn hexdump() {
+        let encoding = Encoding::get_encoding("437").unwrap();
+        let mut grid = ByteGrid::new();
+        for i in 0u16..=std::u16::MAX {
+            grid[i] = (i ^ (i >> 7)) as u8;
+        }
+        let mut out = Vec::new();
+        grid.save_hexdump(&mut out, &encoding).unwrap();
+        let path = write_temp_page("roundtrip.hex", std::str::from_utf8(&out).unwrap());
+        assert!(ByteGrid::load_hexdump(&path).unwrap() == grid);
+
+        let path = write_temp_page("sparse.hex", "# comment\n0102: 41 42 |AB|\n");
+        let loaded = ByteGrid::load_hexdump(&path).unwrap();
+        assert_eq!(loaded[(2, 1)], b'A');
+        assert_eq!(loaded[(3, 1)], b'B');
+        assert_eq!(loaded[(4, 1)], 0);
+
+        let path = write_temp_page("bad.hex", "0000: 41 4x\n");
+        let err = ByteGrid::load_hexdump(&path).err().unwrap();
+        assert_eq!(err.line(), Some(1));
+        assert_eq!(err.column(), Some(10));
+
+        let path = write_temp_page("overflow.hex", "ffff: 41 42\n");
+        assert!(ByteGrid::load_hexdump(&path).is_err());
+    }
+
+    #[test]
+    fn generic_grid() {
+        let mut visits: Grid<u32> = Grid::new();
+        visits[(1, 2)] += 1;
+        visits[V2::make(1, 2)] += 1;
+        visits[0x0102u16] += 1;
+        assert_eq!(visits[(1, 2)], 3);
+
+        let coords = Grid::from_fn(|x, y| (x, y));
+        assert!(coords.iter().all(|(pos, v)| pos == *v));
+        assert_eq!(coords.iter().nth(N + 3).unwrap().0, (3, 1));
+        assert_eq!(coords.row(7)[9], (9, 7));
+
+        let seen = visits.map(|v| *v > 0);
+        assert!(seen[(1, 2)]);
+        assert_eq!(seen.iter().filter(|(_, v)| **v).count(), 1);
+        let sums = visits.zip(&seen, |v, s| *v + *s as u32);
+        assert_eq!(sums[(1, 2)], 4);
+        assert_eq!(sums[(0, 0)], 0);
+
+        let mut notes: Grid<Option<String>> = Grid::default();
+        for (pos, v) in notes.iter_mut() {
+            if pos == (5, 5) {
+                *v = Some("door".to_owned());
+            }
+        }
+        assert_eq!(notes[(5, 5)].as_ref().map(|s| s.as_str()), Some("door"));
+    }
+
+    #[test]
+    fn rect_copy_fill() {
+        let mut grid = ByteGrid::new();
+        grid.fill_rect(
+            &Rectangle {
+                pos: V2::make(-2, 254),
+                size: V2::make(4, 10),
+            },
+            7,
+        );
+        assert_eq!(grid.iter().filter(|(_, v)| **v == 7).count(), 4);
+        assert_eq!(grid[(1, 255)], 7);
+        assert_eq!(grid[(2, 255)], 0);
+
+        let src = Grid::from_fn(|x, y| x ^ y);
+        let mut dst = ByteGrid::new();
+        let rect = Rectangle {
+            pos: V2::make(10, 20),
+            size: V2::make(3, 2),
+        };
+        dst.copy_rect(&src, &rect, V2::make(254, 0));
+        assert_eq!(dst[(254, 0)], src[(10, 20)]);
+        assert_eq!(dst[(255, 1)], src[(11, 21)]);
+        assert_eq!(dst.iter().filter(|(_, v)| **v != 0).count(), 4);
+    }
+
+    #[test]
+    fn extract_blit() {
+        let grid = ByteGrid::from_raw_str(b"abcd\nefgh\nijkl");
+        let rect = Rectangle {
+            pos: V2::make(1, 1),
+            size: V2::make(2, 5),
+        };
+        let rows = grid.extract(&rect);
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0], b"fg".to_vec());
+        assert_eq!(rows[1], b"jk".to_vec());
+        assert_eq!(rows[2], vec![0, 0]);
+
+        let mut target = ByteGrid::new();
+        target.blit(&rows[..2], V2::make(255, -1), 0xff);
+        assert_eq!(target[(255, 0)], b'j');
+        assert_eq!(target.iter().filter(|(_, v)| **v != 0).count(), 1);
+
+        let mut target = ByteGrid::from_raw_str(b"\x0f");
+        target.blit(&[vec![0xf0]], V2::make(0, 0), 0x30);
+        assert_eq!(target[(0, 0)], 0x3f);
+    }
+
     #[test]
     fn test_bits256() {
         let mut a = Bits256::new();
@@ -376,4 +1659,25 @@ mod tests {
         a.clear();
         assert_eq!(a, Bits256::new());
     }
+
+    #[test]
+    fn bits256_sets() {
+        let a: Bits256 = vec![0, 1, 2, 3, 0x42, 0xff].into_iter().collect();
+        let b: Bits256 = vec![3, 4, 0x42].into_iter().collect();
+        assert_eq!(a.count(), 6);
+        assert_eq!(a.iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 0x42, 0xff]);
+        assert_eq!(a.union(&b).count(), 7);
+        assert_eq!(a.intersection(&b).iter().collect::<Vec<_>>(), vec![3, 0x42]);
+        assert_eq!(
+            a.difference(&b).iter().collect::<Vec<_>>(),
+            vec![0, 1, 2, 0xff]
+        );
+        assert!(a.difference(&a).is_empty());
+        assert_eq!(a.to_string(), "00-03,42,ff");
+        assert_eq!(Bits256::new().to_string(), "");
+
+        let yaml = serde_yaml::to_string(&b).unwrap();
+        let parsed: Bits256 = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(parsed, b);
+    }
 }
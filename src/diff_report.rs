@@ -0,0 +1,172 @@
+use std::io::{Result, Write};
+
+use crate::bytegrid::ByteGrid;
+use crate::encoding::Encoding;
+
+/// Horizontal run of changed cells starting at `x`, `y`.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct ChangeRun {
+    pub x: u8,
+    pub y: u8,
+    pub len: usize,
+    /// Old bytes as hex.
+    pub old: String,
+    /// New bytes as hex.
+    pub new: String,
+    /// Old bytes as glyphs in the encoding.
+    pub old_text: String,
+    pub new_text: String,
+}
+
+#[derive(Serialize)]
+pub struct PageReport {
+    pub page: Option<u8>,
+    pub cells: usize,
+    pub runs: Vec<ChangeRun>,
+}
+
+#[derive(Serialize, Default)]
+pub struct DiffSummary {
+    pub pages: usize,
+    pub runs: usize,
+    pub cells: usize,
+}
+
+/// Human readable list of changes between page versions.
+#[derive(Serialize)]
+pub struct DiffReport {
+    pub pages: Vec<PageReport>,
+    pub summary: DiffSummary,
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|v| format!("{:02x}", v))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn page_runs(before: &ByteGrid, after: &ByteGrid, encoding: &Encoding) -> Vec<ChangeRun> {
+    let mut result = Vec::new();
+    let text = |data: &[u8]| -> String {
+        data.iter()
+            .map(|v| encoding.byte_to_char[*v as usize])
+            .collect()
+    };
+    for y in 0..=255u8 {
+        let mut x = 0usize;
+        while x < 256 {
+            if before[(x as u8, y)] == after[(x as u8, y)] {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < 256 && before[(x as u8, y)] != after[(x as u8, y)] {
+                x += 1;
+            }
+            let old: Vec<u8> = (start..x).map(|x| before[(x as u8, y)]).collect();
+            let new: Vec<u8> = (start..x).map(|x| after[(x as u8, y)]).collect();
+            result.push(ChangeRun {
+                x: start as u8,
+                y,
+                len: x - start,
+                old: hex(&old),
+                new: hex(&new),
+                old_text: text(&old),
+                new_text: text(&new),
+            });
+        }
+    }
+    result
+}
+
+impl DiffReport {
+    pub fn new() -> DiffReport {
+        DiffReport {
+            pages: Vec::new(),
+            summary: DiffSummary::default(),
+        }
+    }
+
+    /// Add changes of single page. Unchanged pages are skipped.
+    pub fn add_page(
+        &mut self,
+        page: Option<u8>,
+        before: &ByteGrid,
+        after: &ByteGrid,
+        encoding: &Encoding,
+    ) {
+        let runs = page_runs(before, after, encoding);
+        if runs.is_empty() {
+            return;
+        }
+        let cells = runs.iter().map(|run| run.len).sum();
+        self.summary.pages += 1;
+        self.summary.runs += runs.len();
+        self.summary.cells += cells;
+        self.pages.push(PageReport { page, cells, runs });
+    }
+
+    pub fn write_text(&self, out: &mut Write) -> Result<()> {
+        for page in &self.pages {
+            match page.page {
+                Some(id) => write!(out, "page {:02x}", id)?,
+                None => write!(out, "page")?,
+            }
+            writeln!(out, ": {} cells in {} runs", page.cells, page.runs.len())?;
+            for run in &page.runs {
+                writeln!(
+                    out,
+                    "  {:3},{:3}  - {}  \"{}\"",
+                    run.x, run.y, run.old, run.old_text
+                )?;
+                writeln!(out, "           + {}  \"{}\"", run.new, run.new_text)?;
+            }
+        }
+        writeln!(
+            out,
+            "{} pages changed, {} runs, {} cells",
+            self.summary.pages, self.summary.runs, self.summary.cells
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs() {
+        let encoding = Encoding::get_encoding("437").unwrap();
+        let before = ByteGrid::from_raw_str(b"..........\n..........");
+        let after = ByteGrid::from_raw_str(b"..AB......\n.........X");
+        let mut report = DiffReport::new();
+        report.add_page(Some(0x42), &before, &after, &encoding);
+        report.add_page(Some(0x43), &before, &before, &encoding);
+        assert_eq!(report.summary.pages, 1);
+        assert_eq!(report.summary.runs, 2);
+        assert_eq!(report.summary.cells, 3);
+        assert_eq!(
+            report.pages[0].runs[0],
+            ChangeRun {
+                x: 2,
+                y: 0,
+                len: 2,
+                old: "2e 2e".to_owned(),
+                new: "41 42".to_owned(),
+                old_text: "..".to_owned(),
+                new_text: "AB".to_owned(),
+            }
+        );
+        assert_eq!(
+            (report.pages[0].runs[1].x, report.pages[0].runs[1].y),
+            (9, 1)
+        );
+
+        let mut out = Vec::new();
+        report.write_text(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("page 42: 3 cells in 2 runs\n"));
+        assert!(text.ends_with("1 pages changed, 2 runs, 3 cells\n"));
+    }
+}
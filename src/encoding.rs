@@ -4,6 +4,13 @@ use std::io::{Error, ErrorKind};
 use std::path::{Path, PathBuf};
 use std::str;
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct DecodeError {
+    /// Index of the offending character in the input.
+    pub position: usize,
+    pub c: char,
+}
+
 pub struct Encoding {
     pub byte_to_char: [char; 256],
     pub char_to_byte: HashMap<char, u8>,
@@ -69,7 +76,7 @@ impl Encoding {
         &self,
         mut input: str::Chars<'a>,
         out: &mut [u8],
-    ) -> Result<(usize, &'a str), std::io::Error> {
+    ) -> Result<(usize, &'a str), DecodeError> {
         let mut produced = 0 as usize;
         let n = out.len();
         while produced < n {
@@ -78,7 +85,10 @@ impl Encoding {
                     out[produced] = *byte;
                     produced += 1;
                 } else {
-                    return Err(Error::from(ErrorKind::InvalidData));
+                    return Err(DecodeError {
+                        position: produced,
+                        c,
+                    });
                 }
             } else {
                 return Ok((produced, ""));
@@ -147,5 +157,12 @@ mod tests {
         assert_eq!(&buf, b"12345");
 
         assert!(encoding.decode_utf8("āēūž".chars(), &mut buf).is_err());
+        assert_eq!(
+            encoding.decode_utf8("abžc".chars(), &mut buf),
+            Err(DecodeError {
+                position: 2,
+                c: 'ž'
+            })
+        );
     }
 }
@@ -1,16 +1,23 @@
 use std::collections::HashSet;
 use std::io::Write;
+use std::path::Path;
 
 use termion::color;
 use termion::event::{Event, Key};
 
+use crate::best_results::{BestResults, LevelResult};
+use crate::bytegrid::Grid;
 use crate::encoding::Encoding;
 use crate::gameplay::*;
+use crate::level_list::{HashCache, LevelEntry};
+use crate::load_error::LoadError;
+use crate::search::{SearchMark, SearchQuery, SearchResults};
 use tgame::ui::*;
 use tgame::vecmath::*;
 
 enum GameState {
     MainMenu,
+    LevelSelect,
     Gameplay,
 }
 
@@ -26,6 +33,7 @@ pub struct GameUi {
     id: UiId,
     state: GameState,
     main_menu: Menu,
+    level_list: LevelList,
     gameplay_ui: GamePlayUI,
     result: Option<Result<(), ()>>,
 }
@@ -37,26 +45,54 @@ impl GameUi {
             state: GameState::MainMenu,
             main_menu: {
                 let result = Menu::new(
-                    vec!["New game".to_owned(), "Exit".to_owned()],
+                    vec![
+                        "New game".to_owned(),
+                        "Select level".to_owned(),
+                        "Exit".to_owned(),
+                    ],
                     false,
                     context,
                 );
                 result
             },
+            level_list: LevelList::new(context),
             gameplay_ui: GamePlayUI::new(context),
             result: None,
         }
     }
 
+    fn open_level_list(&mut self, message: Option<String>) -> Option<UiEvent> {
+        let (bests, error) = match BestResults::default_path()
+            .map_err(|e| e.to_string())
+            .and_then(|path| BestResults::load(&path).map_err(|e| e.to_string()))
+        {
+            Ok(bests) => (bests, None),
+            Err(e) => (BestResults::default(), Some(e)),
+        };
+        self.level_list
+            .init(crate::level_list::find_levels(), bests);
+        self.level_list.message = message.or(error);
+        self.state = GameState::LevelSelect;
+        self.event(UiEventType::None)
+    }
+
+    fn start_level(&mut self, game_state: GamePlayState) -> Option<UiEvent> {
+        self.gameplay_ui.set_state(game_state);
+        self.state = GameState::Gameplay;
+        self.event(UiEventType::None)
+    }
+
     fn current_widget_mut(&mut self) -> &mut UiWidget {
         match self.state {
             GameState::MainMenu => &mut self.main_menu,
+            GameState::LevelSelect => &mut self.level_list,
             GameState::Gameplay => &mut self.gameplay_ui,
         }
     }
     fn current_widget(&self) -> &UiWidget {
         match self.state {
             GameState::MainMenu => &self.main_menu,
+            GameState::LevelSelect => &self.level_list,
             GameState::Gameplay => &self.gameplay_ui,
         }
     }
@@ -75,6 +111,7 @@ impl UiWidget for GameUi {
     fn input(&mut self, e: &Event) -> Option<UiEvent> {
         let result = self.current_widget_mut().input(e);
         let main_menu_id = self.main_menu.get_id();
+        let level_list_id = self.level_list.get_id();
         let game_id = self.gameplay_ui.get_id();
         match result {
             None => {
@@ -87,24 +124,16 @@ impl UiWidget for GameUi {
                             if let Ok(v) = selected.downcast::<usize>() {
                                 match *v {
                                     0 => {
-                                        self.state = GameState::Gameplay;
-                                        let game_state = GamePlayState::load_tmp();
-                                        match game_state {
-                                            Ok(gs) => {
-                                                self.gameplay_ui.set_state(gs);
-                                            }
-                                            Err(e) => {
-                                                eprintln!(
-                                                    "{}Failed to load level {:?}",
-                                                    termion::screen::ToMainScreen,
-                                                    e
-                                                );
-                                                return self.event(UiEventType::Canceled);
-                                            }
-                                        }
-                                        return self.event(UiEventType::None);
+                                        return match GamePlayState::load_tmp() {
+                                            Ok(gs) => self.start_level(gs),
+                                            Err(e) => self.open_level_list(Some(format!(
+                                                "Failed to load level {}",
+                                                e
+                                            ))),
+                                        };
                                     }
-                                    1 => return self.event(UiEventType::Canceled),
+                                    1 => return self.open_level_list(None),
+                                    2 => return self.event(UiEventType::Canceled),
                                     _ => {}
                                 }
                             }
@@ -112,9 +141,34 @@ impl UiWidget for GameUi {
                         UiEventType::Canceled => return self.event(UiEventType::Canceled),
                         _ => {}
                     }
+                } else if r.id == level_list_id {
+                    match r.e {
+                        UiEventType::Result(selected) => {
+                            if let Ok(v) = selected.downcast::<usize>() {
+                                let path = self.level_list.levels[*v].path.clone();
+                                return match GamePlayState::load_from_path(&path) {
+                                    Ok(gs) => self.start_level(gs),
+                                    Err(e) => {
+                                        self.level_list.message = Some(e.to_string());
+                                        self.level_list.redraw = true;
+                                        self.event(UiEventType::Changed)
+                                    }
+                                };
+                            }
+                        }
+                        UiEventType::Canceled => {
+                            self.state = GameState::MainMenu;
+                            return self.event(UiEventType::None);
+                        }
+                        _ => {}
+                    }
                 } else if r.id == game_id {
                     match r.e {
-                        UiEventType::Ok | UiEventType::Canceled => {
+                        UiEventType::Ok => {
+                            let summary = self.gameplay_ui.take_summary();
+                            return self.open_level_list(summary);
+                        }
+                        UiEventType::Canceled => {
                             self.state = GameState::MainMenu;
                             return self.event(UiEventType::None);
                         }
@@ -129,6 +183,7 @@ impl UiWidget for GameUi {
 
     fn resize(&mut self, widget_size: &Rectangle) {
         self.main_menu.resize(widget_size);
+        self.level_list.resize(widget_size);
         self.gameplay_ui.resize(widget_size);
     }
 
@@ -137,11 +192,15 @@ impl UiWidget for GameUi {
     }
 
     fn child_widgets(&self) -> Vec<&UiWidget> {
-        vec![&self.main_menu, &self.gameplay_ui]
+        vec![&self.main_menu, &self.level_list, &self.gameplay_ui]
     }
 
     fn child_widgets_mut(&mut self) -> Vec<&mut UiWidget> {
-        vec![&mut self.main_menu, &mut self.gameplay_ui]
+        vec![
+            &mut self.main_menu,
+            &mut self.level_list,
+            &mut self.gameplay_ui,
+        ]
     }
 }
 
@@ -157,6 +216,65 @@ pub struct GamePlayUI {
     need_clean: i32,
     show_encoding: bool,
     encoding_view: EncodingTable,
+    show_pages: bool,
+    page_picker: PagePicker,
+    show_description: bool,
+    description: LevelDescription,
+    search_prompt: Option<SearchPrompt>,
+    search: Option<SearchResults>,
+    search_error: Option<String>,
+    /// Result of last save, shown until next input.
+    status: Option<String>,
+    /// Page and position views are centered on instead of player.
+    focus: Option<(u8, V2)>,
+    /// Hash of level as loaded, key for best results.
+    level_hash: u64,
+    /// Result of finished level, recorded when end of level is reached.
+    summary: Option<String>,
+}
+
+struct SearchPrompt {
+    text: String,
+    all_pages: bool,
+}
+
+/// Page shown by byte and text views.
+#[derive(Clone, Copy)]
+struct PageView<'a> {
+    game: &'a GamePlayState,
+    page: u8,
+    center: V2,
+    marks: Option<&'a Grid<SearchMark>>,
+}
+
+impl<'a> PageView<'a> {
+    fn page_state(&self) -> &'a PageState {
+        self.game.page(self.page)
+    }
+
+    fn is_player(&self, pos: V2) -> bool {
+        self.page == self.game.player_page && self.game.player == PlayerPos::Pos(pos)
+    }
+
+    fn value(&self, pos: V2) -> u8 {
+        if self.page == self.game.player_page {
+            self.game.effective_value(self.page_state(), pos)
+        } else {
+            self.page_state().memory[pos]
+        }
+    }
+
+    fn mark(&self, pos: V2) -> SearchMark {
+        self.marks.map_or(SearchMark::None, |marks| marks[pos])
+    }
+}
+
+fn write_mark(ui: &mut UiContext, mark: SearchMark) -> std::io::Result<()> {
+    match mark {
+        SearchMark::None => Ok(()),
+        SearchMark::Match => write!(ui.raw_out, "{}", color::Bg(color::Magenta)),
+        SearchMark::Current => write!(ui.raw_out, "{}", color::Bg(color::Green)),
+    }
 }
 
 impl GamePlayUI {
@@ -173,11 +291,175 @@ impl GamePlayUI {
             show_encoding: false,
             encoding_view: EncodingTable::new(ui, Encoding::get_encoding("437").unwrap()), //TODO get rid of unwrap
             cpu_view: CpuView::new(ui),
+            show_pages: false,
+            page_picker: PagePicker::new(ui),
+            show_description: false,
+            description: LevelDescription::new(ui),
+            search_prompt: None,
+            search: None,
+            search_error: None,
+            status: None,
+            focus: None,
+            level_hash: 0,
+            summary: None,
         }
     }
 
     pub fn set_state(&mut self, new_state: GamePlayState) {
         self.game = new_state;
+        self.level_hash = self.game.level_hash();
+        self.summary = None;
+        self.search = None;
+        self.search_error = None;
+        self.status = if self.game.load_warnings.is_empty() {
+            None
+        } else {
+            Some(format!("Warning: {}", self.game.load_warnings.join("; ")))
+        };
+        self.focus = None;
+        self.show_description = !self.game.info.is_empty();
+        if self.show_description {
+            self.description.resize(&self.size);
+            self.description.init(&self.game.info);
+        }
+    }
+
+    pub fn take_summary(&mut self) -> Option<String> {
+        self.summary.take()
+    }
+
+    /// Store result of completed level in best results, returns summary for the player.
+    fn finish_level(&self) -> String {
+        let result = LevelResult {
+            moves: self.game.moves,
+            cpu_steps: self.game.cpu_steps,
+        };
+        let summary = format!(
+            "Level complete: {} moves, {} CPU steps",
+            result.moves, result.cpu_steps
+        );
+        let saved = BestResults::default_path()
+            .map_err(|e| e.to_string())
+            .and_then(|path| {
+                let mut bests = BestResults::load(&path).map_err(|e| e.to_string())?;
+                let improved = bests.record(self.level_hash, result);
+                bests.save(&path).map_err(|e| e.to_string())?;
+                Ok(improved)
+            });
+        match saved {
+            Ok(true) => summary + " - new personal best",
+            Ok(false) => summary,
+            Err(e) => format!("{}, failed to save result: {}", summary, e),
+        }
+    }
+
+    /// Save game under `saves` in user data dir.
+    fn save_game<F>(&mut self, name: &str, save: F)
+    where
+        F: Fn(&GamePlayState, &Path) -> Result<(), LoadError>,
+    {
+        let result = crate::resource::get_user_dir()
+            .and_then(|dir| {
+                let dir = dir.join("saves");
+                std::fs::create_dir_all(&dir).map(|_| dir)
+            })
+            .map_err(|e| e.to_string())
+            .and_then(|dir| {
+                let path = dir.join(name);
+                save(&self.game, &path)
+                    .map(|_| path)
+                    .map_err(|e| e.to_string())
+            });
+        self.status = Some(match result {
+            Ok(path) => format!("Saved to {}", path.display()),
+            Err(e) => format!("Save failed: {}", e),
+        });
+    }
+
+    fn run_search(&mut self, prompt: SearchPrompt) {
+        self.focus = None;
+        self.search = None;
+        self.search_error = None;
+        let query = match SearchQuery::parse(&prompt.text, &self.text_view.encoding) {
+            Ok(query) => query,
+            Err(e) => {
+                self.search_error = Some(e);
+                return;
+            }
+        };
+        let player_page = self.game.player_page;
+        let pages = self
+            .game
+            .pages
+            .iter()
+            .filter(|(id, _)| prompt.all_pages || **id == player_page)
+            .map(|(id, page)| (*id, &page.memory));
+        let results = SearchResults::new(&query, pages);
+        if results.matches.is_empty() {
+            self.search_error = Some(format!("No matches for '{}'", prompt.text));
+        } else {
+            self.search = Some(results);
+            self.focus_match();
+        }
+    }
+
+    fn focus_match(&mut self) {
+        if let Some(m) = self.search.as_ref().and_then(|search| search.current()) {
+            self.focus = Some((m.page, m.pos));
+        }
+    }
+
+    fn search_input(&mut self, e: &Event) {
+        let mut prompt = match self.search_prompt.take() {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match e {
+            Event::Key(Key::Char('\n')) => {
+                self.run_search(prompt);
+                return;
+            }
+            Event::Key(Key::Esc) => return,
+            Event::Key(Key::Char('\t')) => prompt.all_pages = !prompt.all_pages,
+            Event::Key(Key::Backspace) => {
+                prompt.text.pop();
+            }
+            Event::Key(Key::Char(c)) => prompt.text.push(*c),
+            _ => {}
+        }
+        self.search_prompt = Some(prompt);
+    }
+
+    fn print_search_line(&self, ui: &mut UiContext) -> std::io::Result<()> {
+        if let Some(prompt) = &self.search_prompt {
+            write!(
+                ui.raw_out,
+                "Search {} (Tab: scope, d: x: b: prefixes): {}",
+                if prompt.all_pages {
+                    "all pages"
+                } else {
+                    "this page"
+                },
+                prompt.text
+            )?;
+        } else if let Some(status) = &self.status {
+            write!(ui.raw_out, "{}", status)?;
+        } else if let Some(e) = &self.search_error {
+            write!(ui.raw_out, "Search: {}", e)?;
+        } else if let Some(search) = &self.search {
+            if let Some(m) = search.current() {
+                write!(
+                    ui.raw_out,
+                    "Match {}/{} on page {:02x} at {},{} (n/N to cycle, Esc to clear)",
+                    search.current_index() + 1,
+                    search.matches.len(),
+                    m.page,
+                    m.pos.x,
+                    m.pos.y
+                )?;
+            }
+        }
+        Ok(())
     }
 
     fn player_print_pos(&self) -> V2 {
@@ -274,13 +556,30 @@ impl GamePlayUI {
                 write!(ui.raw_out, "Player position: Register")?;
             }
         }
+        write!(ui.raw_out, "  Moves: {}", self.game.moves)?;
+        if let Some(par) = self.game.info.par_moves {
+            write!(ui.raw_out, " (par {})", par)?;
+        }
+        write!(ui.raw_out, " CPU steps: {}", self.game.cpu_steps)?;
+        write!(ui.raw_out, "{}\r\n", termion::clear::UntilNewline)?;
+        let mut left = self.game.info.heading().unwrap_or_default();
+        if let ByteViewMode::Plane(plane) = self.byte_view.mode {
+            if !left.is_empty() {
+                left.push_str("  ");
+            }
+            left.push_str(&format!("Bit plane: {} ([ ] to change)", plane));
+        }
+        let left_width = std::cmp::max(middle.x - self.size.pos.x - 1, 0) as usize;
         write!(
             ui.raw_out,
-            "{}\r\n{}\r\n{}",
-            termion::clear::UntilNewline,
-            termion::clear::UntilNewline,
-            termion::clear::UntilNewline
+            "{}",
+            left.chars().take(left_width).collect::<String>()
         )?;
+        ui.goto(middle + V2::make(0, 1))?;
+        write!(ui.raw_out, "Visited pages: {}", self.game.visited_pages())?;
+        write!(ui.raw_out, "{}\r\n", termion::clear::UntilNewline)?;
+        self.print_search_line(ui)?;
+        write!(ui.raw_out, "{}", termion::clear::UntilNewline)?;
         Ok(())
     }
 
@@ -291,6 +590,10 @@ impl GamePlayUI {
     fn get_popup_mut(&mut self) -> Option<&mut UiWidget> {
         if self.show_encoding {
             Some(&mut self.encoding_view)
+        } else if self.show_pages {
+            Some(&mut self.page_picker)
+        } else if self.show_description {
+            Some(&mut self.description)
         } else {
             None
         }
@@ -307,10 +610,18 @@ impl UiWidget for GamePlayUI {
             }
             self.print_top_panel(ui)?;
             self.print_edges(ui)?;
-            self.byte_view
-                .print_data(ui, (&self.game, self.player_print_pos()))?;
-            self.text_view
-                .print_data(ui, (&self.game, self.player_print_pos()))?;
+            let (page, center) = self
+                .focus
+                .unwrap_or((self.game.player_page, self.player_print_pos()));
+            let marks = self.search.as_ref().map(|search| search.marks(page));
+            let view = PageView {
+                game: &self.game,
+                page,
+                center,
+                marks: marks.as_ref(),
+            };
+            self.byte_view.print_data(ui, view)?;
+            self.text_view.print_data(ui, view)?;
             self.cpu_view.print_data(ui, &self.game)?;
         }
         ui.raw_out.flush()?;
@@ -332,7 +643,64 @@ impl UiWidget for GamePlayUI {
             }
             return None;
         }
+        if self.show_pages {
+            match self.page_picker.input(e) {
+                Some(UiEvent {
+                    id: _,
+                    e: UiEventType::Result(page),
+                }) => {
+                    if let Ok(page) = page.downcast::<u8>() {
+                        self.focus = None;
+                        self.game.make_move(PlayerMove::JumpToPage(*page));
+                    }
+                    self.show_pages = false;
+                    self.need_clean = 2;
+                    return self.event(UiEventType::None);
+                }
+                Some(UiEvent {
+                    id: _,
+                    e: UiEventType::Canceled,
+                }) => {
+                    self.show_pages = false;
+                    self.need_clean = 2;
+                    return self.event(UiEventType::None);
+                }
+                _ => {}
+            }
+            return None;
+        }
+        if self.show_description {
+            if let Some(UiEvent {
+                id: _,
+                e: UiEventType::Canceled,
+            }) = self.description.input(e)
+            {
+                self.show_description = false;
+                self.need_clean = 2;
+                return self.event(UiEventType::None);
+            }
+            return None;
+        }
+        if self.search_prompt.is_some() {
+            self.search_input(e);
+            return self.event(UiEventType::None);
+        }
+        self.status = None;
         //TODO:keybindings
+        match e {
+            Event::Key(Key::Up)
+            | Event::Key(Key::Char('k'))
+            | Event::Key(Key::Left)
+            | Event::Key(Key::Char('h'))
+            | Event::Key(Key::Down)
+            | Event::Key(Key::Char('j'))
+            | Event::Key(Key::Right)
+            | Event::Key(Key::Char('l'))
+            | Event::Key(Key::Char('a')) => {
+                self.focus = None;
+            }
+            _ => {}
+        }
         match e {
             Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
                 self.game.make_move(PlayerMove::Move(MoveDir::Up));
@@ -349,6 +717,16 @@ impl UiWidget for GamePlayUI {
             Event::Key(Key::Char('a')) => {
                 self.game.make_move(PlayerMove::RotatePage);
             }
+            Event::Key(Key::Char('g')) => {
+                self.show_pages = true;
+                self.page_picker.resize(&self.size);
+                self.page_picker.init(&self.game);
+            }
+            Event::Key(Key::Char('i')) => {
+                self.show_description = true;
+                self.description.resize(&self.size);
+                self.description.init(&self.game.info);
+            }
             Event::Key(Key::Char('x')) => {
                 self.show_encoding = true;
                 self.encoding_view.resize(&self.size);
@@ -356,16 +734,56 @@ impl UiWidget for GamePlayUI {
             }
             Event::Key(Key::Char('p')) => {
                 self.byte_view.mode = match self.byte_view.mode {
-                    ByteViewMode::Hex => ByteViewMode::Bits,
                     ByteViewMode::Bits => ByteViewMode::Hex,
+                    ByteViewMode::Hex => ByteViewMode::Plane(self.game.player_offset),
+                    ByteViewMode::Plane(_) => ByteViewMode::Bits,
                 };
+                self.need_clean = 2;
+            }
+            Event::Key(Key::Char(']')) => {
+                self.byte_view.cycle_plane(1);
+            }
+            Event::Key(Key::Char('[')) => {
+                self.byte_view.cycle_plane(7);
             }
             Event::Key(Key::Char('b')) => {
                 self.text_view.show_positions = !self.text_view.show_positions;
             }
+            Event::Key(Key::Char('/')) => {
+                self.search_prompt = Some(SearchPrompt {
+                    text: String::new(),
+                    all_pages: false,
+                });
+            }
+            Event::Key(Key::Char('n')) => {
+                if let Some(search) = &mut self.search {
+                    search.next();
+                }
+                self.focus_match();
+            }
+            Event::Key(Key::Char('N')) => {
+                if let Some(search) = &mut self.search {
+                    search.prev();
+                }
+                self.focus_match();
+            }
+            Event::Key(Key::Char('S')) => {
+                self.save_game("quicksave", GamePlayState::save_to_folder);
+            }
+            Event::Key(Key::Char('E')) => {
+                self.save_game("export.storage", GamePlayState::save_rbstorage);
+            }
+            Event::Key(Key::Esc) => {
+                self.search = None;
+                self.search_error = None;
+                self.focus = None;
+            }
             _ => {}
         }
         if self.game.end_of_level {
+            if self.summary.is_none() {
+                self.summary = Some(self.finish_level());
+            }
             return self.event(UiEventType::Ok);
         }
         return self.event(UiEventType::None);
@@ -376,6 +794,8 @@ impl UiWidget for GamePlayUI {
             &self.byte_view,
             &self.text_view,
             &self.encoding_view,
+            &self.page_picker,
+            &self.description,
             &self.cpu_view,
         ]
     }
@@ -385,6 +805,8 @@ impl UiWidget for GamePlayUI {
             &mut self.byte_view,
             &mut self.text_view,
             &mut self.encoding_view,
+            &mut self.page_picker,
+            &mut self.description,
             &mut self.cpu_view,
         ]
     }
@@ -452,9 +874,12 @@ impl UiWidget for GamePlayUI {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
 enum ByteViewMode {
     Bits,
     Hex,
+    /// Single bit of each byte drawn as solid or empty cell.
+    Plane(u8),
 }
 
 struct ByteView {
@@ -471,6 +896,12 @@ impl ByteView {
             mode: ByteViewMode::Bits,
         }
     }
+
+    fn cycle_plane(&mut self, step: u8) {
+        if let ByteViewMode::Plane(plane) = self.mode {
+            self.mode = ByteViewMode::Plane((plane + step) % 8);
+        }
+    }
 }
 
 fn print_byte_as_bits(
@@ -499,17 +930,16 @@ fn print_byte_as_bits(
     }
 }
 
-impl DataWidget<(&GamePlayState, V2)> for ByteView {
-    fn print_data(
-        &mut self,
-        ui: &mut UiContext,
-        (data, player): (&GamePlayState, V2),
-    ) -> std::io::Result<()> {
-        let block_width = match self.mode {
-            ByteViewMode::Bits => 8,
-            ByteViewMode::Hex => 2,
+impl<'a> DataWidget<PageView<'a>> for ByteView {
+    fn print_data(&mut self, ui: &mut UiContext, view: PageView<'a>) -> std::io::Result<()> {
+        let data = view.game;
+        let player = view.center;
+        let (block_width, separator) = match self.mode {
+            ByteViewMode::Bits => (8, 1),
+            ByteViewMode::Hex => (2, 1),
+            ByteViewMode::Plane(_) => (1, 0),
         };
-        let block_count = (self.size.size.x + 1) / (block_width + 1);
+        let block_count = (self.size.size.x + separator) / (block_width + separator);
         for y in 0..self.size.size.y {
             ui.goto(self.size.pos + V2::make(0, y))?;
             let my = player.y + y - (self.size.size.y / 2);
@@ -519,7 +949,7 @@ impl DataWidget<(&GamePlayState, V2)> for ByteView {
                 let mut px = 0;
                 for block_id in 0..block_count {
                     let mx = player.x + block_id - (block_count / 2);
-                    if block_id > 0 {
+                    if block_id > 0 && separator > 0 {
                         write!(ui.raw_out, " ")?;
                         px += 1;
                     }
@@ -528,8 +958,10 @@ impl DataWidget<(&GamePlayState, V2)> for ByteView {
                         write!(ui.raw_out, "{:1$}", " ", block_width as usize)?;
                     } else {
                         let pos = V2::make(mx, my);
-                        let byte = data.effective_value(data.current_page(), pos);
-                        let is_player_pos = data.player == PlayerPos::Pos(pos);
+                        let byte = view.value(pos);
+                        let is_player_pos = view.is_player(pos);
+                        let mark = view.mark(pos);
+                        write_mark(ui, mark)?;
                         if !is_player_pos {
                             if data.accessible(byte) {
                                 write!(ui.raw_out, "{}", color::Fg(color::Reset))?;
@@ -557,6 +989,25 @@ impl DataWidget<(&GamePlayState, V2)> for ByteView {
                                 }
                                 write!(ui.raw_out, "{:02x}", byte)?;
                             }
+                            ByteViewMode::Plane(plane) => {
+                                let set = byte & (1 << plane) != 0;
+                                let c = if is_player_pos {
+                                    write!(ui.raw_out, "{}", color::Fg(color::Yellow))?;
+                                    if set {
+                                        '█'
+                                    } else {
+                                        '·'
+                                    }
+                                } else if set {
+                                    '█'
+                                } else {
+                                    ' '
+                                };
+                                write!(ui.raw_out, "{}", c)?;
+                            }
+                        }
+                        if mark != SearchMark::None {
+                            write!(ui.raw_out, "{}", color::Bg(color::Reset))?;
                         }
                     }
                     px += block_width;
@@ -627,14 +1078,16 @@ impl TextView {
     }
 }
 
-impl DataWidget<(&GamePlayState, V2)> for TextView {
-    fn print_data(
-        &mut self,
-        ui: &mut UiContext,
-        (data, last_pos): (&GamePlayState, V2),
-    ) -> std::io::Result<()> {
+impl<'a> DataWidget<PageView<'a>> for TextView {
+    fn print_data(&mut self, ui: &mut UiContext, view: PageView<'a>) -> std::io::Result<()> {
+        let data = view.game;
+        let last_pos = view.center;
         let mut buf = [0u8; 16];
-        let operand_positions = self.get_operand_addresses(data);
+        let operand_positions = if view.page == data.player_page {
+            self.get_operand_addresses(data)
+        } else {
+            HashSet::new()
+        };
         for y in 0..self.size.size.y {
             ui.goto(self.size.pos + V2::make(0, y))?;
             let my = last_pos.y + y - (self.size.size.y / 2);
@@ -648,17 +1101,18 @@ impl DataWidget<(&GamePlayState, V2)> for TextView {
                         write!(ui.raw_out, " ")?;
                     } else {
                         let pos = V2::make(mx, my);
-                        let byte = data.effective_value(data.current_page(), pos);
-                        let is_player_pos = data.player == PlayerPos::Pos(pos);
+                        let byte = view.value(pos);
+                        let is_player_pos = view.is_player(pos);
                         let c = self.encoding.byte_to_char[byte as usize];
                         let str = c.encode_utf8(&mut buf);
 
                         let has_trigger =
-                            if let Some(trig) = data.current_page().triggers.get(&joinu16(pos)) {
+                            if let Some(trig) = view.page_state().triggers.get(&joinu16(pos)) {
                                 trig.is_active()
                             } else {
                                 false
                             };
+                        let mark = view.mark(pos);
                         let is_marked =
                             self.show_positions && operand_positions.contains(&joinu16(pos));
 
@@ -671,11 +1125,12 @@ impl DataWidget<(&GamePlayState, V2)> for TextView {
                         if has_trigger {
                             write!(ui.raw_out, "{}", color::Bg(color::LightRed))?;
                         }
+                        write_mark(ui, mark)?;
                         ui.raw_out.write_all(str.as_bytes())?;
                         if is_player_pos {
                             write!(ui.raw_out, "{}", color::Fg(color::Reset))?;
                         }
-                        if has_trigger || is_marked {
+                        if has_trigger || is_marked || mark != SearchMark::None {
                             write!(ui.raw_out, "{}", color::Bg(color::Reset))?;
                         }
                     }
@@ -709,6 +1164,421 @@ impl UiWidget for TextView {
     }
 }
 
+/// Level selection screen, returns index of selected level as `usize` result.
+struct LevelList {
+    id: UiId,
+    size: Rectangle,
+    redraw: bool,
+    levels: Vec<LevelEntry>,
+    /// Level hashes for best results, updated when the list is opened.
+    hashes: HashCache,
+    bests: BestResults,
+    selected: usize,
+    offset: usize,
+    /// Load error or result of finished level, shown until next input.
+    message: Option<String>,
+}
+
+impl LevelList {
+    fn new(ui: &mut UiContext) -> LevelList {
+        LevelList {
+            id: ui.next_id(),
+            size: DEFAULT_WINDOW_SIZE,
+            redraw: true,
+            levels: Vec::new(),
+            hashes: HashCache::default(),
+            bests: BestResults::default(),
+            selected: 0,
+            offset: 0,
+            message: None,
+        }
+    }
+
+    fn init(&mut self, levels: Vec<LevelEntry>, bests: BestResults) {
+        self.hashes.refresh(&levels);
+        self.levels = levels;
+        self.bests = bests;
+        self.selected = 0;
+        self.offset = 0;
+        self.redraw = true;
+    }
+
+    fn visible_rows(&self) -> usize {
+        std::cmp::max(self.size.size.y - 5, 1) as usize
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        let rows = self.visible_rows();
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + rows {
+            self.offset = self.selected + 1 - rows;
+        }
+        self.redraw = true;
+    }
+}
+
+impl UiWidget for LevelList {
+    fn print(&mut self, ui: &mut UiContext) -> std::io::Result<()> {
+        if !self.redraw {
+            return Ok(());
+        }
+        self.redraw = false;
+
+        write!(ui.raw_out, "{}", ::termion::clear::All)?;
+        ui.goto(self.size.pos)?;
+        write!(
+            ui.raw_out,
+            "{:<32} {:<8} {:<8} {}",
+            "LEVEL", "FORMAT", "SOURCE", "BEST MOVES/CPU STEPS"
+        )?;
+        let rows = self.visible_rows();
+        if self.levels.is_empty() {
+            ui.goto(self.size.pos + V2::make(0, 1))?;
+            write!(ui.raw_out, "No levels found")?;
+        }
+        for (row, level) in self.levels.iter().enumerate().skip(self.offset).take(rows) {
+            ui.goto(self.size.pos + V2::make(0, (row - self.offset) as i32 + 1))?;
+            if row == self.selected {
+                write!(ui.raw_out, "{}", color::Bg(color::Blue))?;
+            }
+            let best = match self
+                .hashes
+                .get(&level.path)
+                .and_then(|hash| self.bests.get(hash))
+            {
+                Some(best) => format!("{}/{}", best.moves, best.cpu_steps),
+                None => "-".to_owned(),
+            };
+            write!(
+                ui.raw_out,
+                "{:<32} {:<8} {:<8} {}{}",
+                level.name,
+                level.format.name(),
+                level.source,
+                best,
+                color::Bg(color::Reset)
+            )?;
+        }
+        ui.goto(self.size.pos + V2::make(0, rows as i32 + 2))?;
+        write!(ui.raw_out, "Enter: play, Esc: back")?;
+        if let Some(message) = &self.message {
+            ui.goto(self.size.pos + V2::make(0, rows as i32 + 3))?;
+            write!(
+                ui.raw_out,
+                "{}{}{}",
+                color::Fg(color::Yellow),
+                message,
+                color::Fg(color::Reset)
+            )?;
+        }
+        Ok(())
+    }
+
+    fn input(&mut self, e: &Event) -> Option<UiEvent> {
+        if self.message.take().is_some() {
+            self.redraw = true;
+        }
+        match e {
+            Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => self.event(UiEventType::Canceled),
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                if self.selected > 0 {
+                    let selected = self.selected - 1;
+                    self.select(selected);
+                }
+                self.event(UiEventType::Changed)
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j')) => {
+                if self.selected + 1 < self.levels.len() {
+                    let selected = self.selected + 1;
+                    self.select(selected);
+                }
+                self.event(UiEventType::Changed)
+            }
+            Event::Key(Key::Char('\n')) if self.selected < self.levels.len() => {
+                self.event(UiEventType::Result(Box::new(self.selected)))
+            }
+            _ => None,
+        }
+    }
+
+    fn child_widgets(&self) -> Vec<&UiWidget> {
+        Vec::new()
+    }
+
+    fn child_widgets_mut(&mut self) -> Vec<&mut UiWidget> {
+        Vec::new()
+    }
+
+    fn resize(&mut self, widget_size: &Rectangle) {
+        self.size = *widget_size;
+        self.redraw = true;
+    }
+
+    fn get_id(&self) -> UiId {
+        self.id
+    }
+}
+
+/// Popup with level title, author and description, closed by any key.
+struct LevelDescription {
+    id: UiId,
+    size: Rectangle,
+    redraw: bool,
+    lines: Vec<String>,
+}
+
+/// Split text into lines no longer than `width`, breaking at spaces where possible.
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let width = std::cmp::max(width, 1);
+    let mut result = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word: Vec<char> = word.chars().collect();
+            while !word.is_empty() {
+                let line_len = line.chars().count();
+                let space = if line_len > 0 { 1 } else { 0 };
+                if line_len + space + word.len() <= width {
+                    if space > 0 {
+                        line.push(' ');
+                    }
+                    line.extend(word.drain(..));
+                } else if line_len > 0 {
+                    result.push(std::mem::replace(&mut line, String::new()));
+                } else {
+                    line.extend(word.drain(..width));
+                    result.push(std::mem::replace(&mut line, String::new()));
+                }
+            }
+        }
+        result.push(line);
+    }
+    result
+}
+
+impl LevelDescription {
+    fn new(ui: &mut UiContext) -> LevelDescription {
+        LevelDescription {
+            id: ui.next_id(),
+            size: DEFAULT_WINDOW_SIZE,
+            redraw: true,
+            lines: Vec::new(),
+        }
+    }
+
+    fn init(&mut self, info: &LevelInfo) {
+        let width = std::cmp::max(self.size.size.x, 1) as usize;
+        self.lines = Vec::new();
+        self.lines.push(
+            info.heading()
+                .unwrap_or_else(|| "Untitled level".to_owned()),
+        );
+        if let Some(difficulty) = &info.difficulty {
+            self.lines.push(format!("Difficulty: {}", difficulty));
+        }
+        if let Some(par) = info.par_moves {
+            self.lines.push(format!("Par: {} moves", par));
+        }
+        if let Some(description) = &info.description {
+            self.lines.push(String::new());
+            self.lines.extend(wrap_text(description, width));
+        }
+        self.redraw = true;
+    }
+}
+
+impl UiWidget for LevelDescription {
+    fn print(&mut self, ui: &mut UiContext) -> std::io::Result<()> {
+        if !self.redraw {
+            return Ok(());
+        }
+        self.redraw = false;
+
+        write!(ui.raw_out, "{}", ::termion::clear::All)?;
+        let rows = std::cmp::max(self.size.size.y - 2, 0) as usize;
+        for (row, line) in self.lines.iter().take(rows).enumerate() {
+            ui.goto(self.size.pos + V2::make(0, row as i32))?;
+            write!(ui.raw_out, "{}", line)?;
+        }
+        ui.goto(self.size.pos + V2::make(0, rows as i32 + 1))?;
+        write!(ui.raw_out, "Press any key to continue")?;
+        Ok(())
+    }
+
+    fn input(&mut self, e: &Event) -> Option<UiEvent> {
+        match e {
+            Event::Key(_) => self.event(UiEventType::Canceled),
+            _ => None,
+        }
+    }
+
+    fn child_widgets(&self) -> Vec<&UiWidget> {
+        Vec::new()
+    }
+
+    fn child_widgets_mut(&mut self) -> Vec<&mut UiWidget> {
+        Vec::new()
+    }
+
+    fn resize(&mut self, widget_size: &Rectangle) {
+        self.size = *widget_size;
+        self.redraw = true;
+    }
+
+    fn get_id(&self) -> UiId {
+        self.id
+    }
+}
+
+/// Popup listing loaded pages, returns selected page id as `u8` result.
+struct PagePicker {
+    id: UiId,
+    size: Rectangle,
+    redraw: bool,
+    pages: Vec<PageInfo>,
+    player_page: u8,
+    selected: usize,
+    offset: usize,
+}
+
+impl PagePicker {
+    fn new(ui: &mut UiContext) -> PagePicker {
+        PagePicker {
+            id: ui.next_id(),
+            size: DEFAULT_WINDOW_SIZE,
+            redraw: true,
+            pages: Vec::new(),
+            player_page: 0,
+            selected: 0,
+            offset: 0,
+        }
+    }
+
+    fn init(&mut self, game: &GamePlayState) {
+        self.pages = game.page_list();
+        self.player_page = game.player_page;
+        self.selected = self
+            .pages
+            .iter()
+            .position(|page| page.id == game.player_page)
+            .unwrap_or(0);
+        self.offset = 0;
+        self.redraw = true;
+    }
+
+    fn visible_rows(&self) -> usize {
+        std::cmp::max(self.size.size.y - 4, 1) as usize
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        let rows = self.visible_rows();
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + rows {
+            self.offset = self.selected + 1 - rows;
+        }
+        self.redraw = true;
+    }
+}
+
+impl UiWidget for PagePicker {
+    fn print(&mut self, ui: &mut UiContext) -> std::io::Result<()> {
+        if !self.redraw {
+            return Ok(());
+        }
+        self.redraw = false;
+
+        write!(ui.raw_out, "{}", ::termion::clear::All)?;
+        ui.goto(self.size.pos)?;
+        write!(ui.raw_out, "PAGE VISITED CPU CODE")?;
+        let rows = self.visible_rows();
+        for (row, page) in self.pages.iter().enumerate().skip(self.offset).take(rows) {
+            ui.goto(self.size.pos + V2::make(0, (row - self.offset) as i32 + 1))?;
+            if row == self.selected {
+                write!(ui.raw_out, "{}", color::Bg(color::Blue))?;
+            }
+            if !page.reachable {
+                write!(ui.raw_out, "{}", color::Fg(color::LightBlack))?;
+            }
+            let flag = |v: bool| if v { "yes" } else { "-" };
+            write!(
+                ui.raw_out,
+                "{}{:02x}  {:>7} {:>3} {:>4}",
+                if page.id == self.player_page {
+                    "*"
+                } else {
+                    " "
+                },
+                page.id,
+                flag(page.visited),
+                flag(page.cpu),
+                flag(page.has_code)
+            )?;
+            write!(
+                ui.raw_out,
+                "{}{}",
+                color::Fg(color::Reset),
+                color::Bg(color::Reset)
+            )?;
+        }
+        ui.goto(self.size.pos + V2::make(0, rows as i32 + 2))?;
+        write!(ui.raw_out, "Enter: go to page, Esc: close")?;
+        if let Some(page) = self.pages.get(self.selected) {
+            if !page.reachable {
+                write!(ui.raw_out, " (page change not allowed)")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn input(&mut self, e: &Event) -> Option<UiEvent> {
+        match e {
+            Event::Key(Key::Char('g')) | Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => {
+                self.event(UiEventType::Canceled)
+            }
+            Event::Key(Key::Up) | Event::Key(Key::Char('k')) => {
+                if self.selected > 0 {
+                    let selected = self.selected - 1;
+                    self.select(selected);
+                }
+                self.event(UiEventType::Changed)
+            }
+            Event::Key(Key::Down) | Event::Key(Key::Char('j')) => {
+                if self.selected + 1 < self.pages.len() {
+                    let selected = self.selected + 1;
+                    self.select(selected);
+                }
+                self.event(UiEventType::Changed)
+            }
+            Event::Key(Key::Char('\n')) => match self.pages.get(self.selected) {
+                Some(page) if page.reachable => self.event(UiEventType::Result(Box::new(page.id))),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn child_widgets(&self) -> Vec<&UiWidget> {
+        Vec::new()
+    }
+
+    fn child_widgets_mut(&mut self) -> Vec<&mut UiWidget> {
+        Vec::new()
+    }
+
+    fn resize(&mut self, widget_size: &Rectangle) {
+        self.size = *widget_size;
+        self.redraw = true;
+    }
+
+    fn get_id(&self) -> UiId {
+        self.id
+    }
+}
+
 struct EncodingTable {
     id: UiId,
     size: Rectangle,
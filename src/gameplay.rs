@@ -1,17 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::prelude::*;
-use std::io::{Error, ErrorKind};
-use std::path::Path;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 use crate::bytegrid::*;
 use crate::encoding::Encoding;
-use crate::serde_rbbin::RBSave;
+use crate::load_error::{LoadError, LoadErrorKind};
+use crate::region;
+use crate::serde_rbbin::{RBJump, RBSave};
 use tgame::vecmath::*;
 
 const GRID_MAX: u8 = 0xff;
 const PLAYER_VAL: u8 = b'@';
-const PLAYER_OFFSET: usize = 6;
-const DEFAULT_PAGE: u8 = 0x42;
+pub const PLAYER_OFFSET: usize = 6;
+pub const DEFAULT_PAGE: u8 = 0x42;
+/// Achievement of RB jump that ends the level.
+const RB_WIN_ACHIEVEMENT: &str = "WIN";
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum PlayerPos {
@@ -25,7 +29,7 @@ impl PlayerPos {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 enum TriggerKind {
     SetPC(u16),
     EndOfLevel,
@@ -54,6 +58,49 @@ impl Trigger {
     pub fn is_active(&self) -> bool {
         !self.triggered || !self.one_time
     }
+
+    pub fn pos(&self) -> V2 {
+        self.pos
+    }
+
+    /// Readable form of trigger effect.
+    pub fn effect_text(&self) -> String {
+        let text = match &self.effect {
+            TriggerKind::SetPC(pc) => format!("SetPC({:04x})", pc),
+            TriggerKind::EndOfLevel => "EndOfLevel".to_owned(),
+            TriggerKind::Message(m) => format!("Message({:?})", m),
+        };
+        if self.one_time {
+            text
+        } else {
+            text + " repeating"
+        }
+    }
+
+    /// Compare effect and repetition, "WIN" message counts as end of level like when
+    /// triggered.
+    pub fn same_effect(&self, other: &Trigger) -> bool {
+        let normalized = |kind: &TriggerKind| match kind {
+            TriggerKind::Message(m) if m == RB_WIN_ACHIEVEMENT => TriggerKind::EndOfLevel,
+            kind => kind.clone(),
+        };
+        normalized(&self.effect) == normalized(&other.effect) && self.one_time == other.one_time
+    }
+
+    /// Trigger shifted by `offset`. Jump targets inside `area` move along with it.
+    pub fn moved(&self, area: &Rectangle, offset: V2) -> Trigger {
+        let effect = match &self.effect {
+            TriggerKind::SetPC(target) if region::contains(area, splitu16(*target)) => {
+                TriggerKind::SetPC(joinu16(splitu16(*target) + offset))
+            }
+            effect => effect.clone(),
+        };
+        Trigger {
+            pos: self.pos + offset,
+            effect,
+            ..self.clone()
+        }
+    }
 }
 
 pub struct PageState {
@@ -122,7 +169,7 @@ impl PageState {
     }
 }
 
-#[derive(Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Clone, Copy)]
 enum PageRotationRule {
     Always,
     Never,
@@ -135,7 +182,7 @@ impl Default for PageRotationRule {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct GameRules {
     #[serde(default)]
     wrap_mode: WrapingMode,
@@ -183,6 +230,16 @@ pub struct GamePlayState {
     null_page: PageState,
     page_instruction_executed: bool,
     pub end_of_level: bool,
+    /// Parts of level file that couldn't be used.
+    pub load_warnings: Vec<String>,
+    pub info: LevelInfo,
+    /// Player moves that advanced the world, including blocked ones.
+    pub moves: u32,
+    pub cpu_steps: u64,
+    /// `playerLocation` and `cameraBottom` of imported Rogue Bit save, only written back
+    /// on export.
+    rb_player_location: i32,
+    rb_camera_bottom: i32,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -197,6 +254,21 @@ pub enum MoveDir {
 pub enum PlayerMove {
     Move(MoveDir),
     RotatePage,
+    /// Switch directly to visited page, same restrictions as `RotatePage`.
+    JumpToPage(u8),
+}
+
+/// Page summary for page selection.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PageInfo {
+    pub id: u8,
+    pub visited: bool,
+    /// Some CPU has the page selected.
+    pub cpu: bool,
+    /// Page has at least two instructions following each other in execution order.
+    pub has_code: bool,
+    /// Player is allowed to switch to page right now.
+    pub reachable: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
@@ -247,12 +319,24 @@ fn step(p0: V2, d: MoveDir, mode: WrapingMode) -> V2 {
     }
 }
 
-enum LevelFormat {
-    SingleGrid,
+pub enum LevelFormat {
+    SingleGrid(PageFormat),
     Folder,
     RBStorage,
 }
 
+impl LevelFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LevelFormat::SingleGrid(PageFormat::Text) => "text",
+            LevelFormat::SingleGrid(PageFormat::Binary(_)) => "binary",
+            LevelFormat::SingleGrid(PageFormat::HexDump) => "hexdump",
+            LevelFormat::Folder => "folder",
+            LevelFormat::RBStorage => "storage",
+        }
+    }
+}
+
 impl GamePlayState {
     pub fn new() -> GamePlayState {
         GamePlayState {
@@ -266,6 +350,12 @@ impl GamePlayState {
             visited_pages: Bits256::new(),
             page_instruction_executed: false,
             end_of_level: false,
+            load_warnings: Vec::new(),
+            info: LevelInfo::default(),
+            moves: 0,
+            cpu_steps: 0,
+            rb_player_location: 0,
+            rb_camera_bottom: 0,
         }
     }
 
@@ -309,13 +399,17 @@ impl GamePlayState {
         state
     }
 
-    pub fn load_tmp() -> std::io::Result<GamePlayState> {
-        GamePlayState::load_from_path(&crate::resource::get_resource_dir()?.join("levels/rb"))
+    pub fn load_tmp() -> Result<GamePlayState, LoadError> {
+        let level_path = Path::new("levels/rb");
+        let resource_dir =
+            crate::resource::get_resource_dir().map_err(|e| LoadError::io(level_path, e))?;
+        GamePlayState::load_from_path(&resource_dir.join(level_path))
     }
 
-    pub fn single_from_path(path: &Path) -> std::io::Result<GamePlayState> {
-        let encoding = crate::encoding::Encoding::get_encoding("437")?;
-        let grid = ByteGrid::load(path, &encoding)?;
+    pub fn single_from_path(path: &Path) -> Result<GamePlayState, LoadError> {
+        let encoding =
+            crate::encoding::Encoding::get_encoding("437").map_err(|e| LoadError::io(path, e))?;
+        let grid = ByteGrid::load_format(path, PageFormat::from_path(path), &encoding, false)?;
         Ok(GamePlayState::from_grid(grid))
     }
 
@@ -333,7 +427,7 @@ impl GamePlayState {
         state
     }
 
-    fn detect_level_format(path: &Path) -> std::io::Result<LevelFormat> {
+    pub fn detect_level_format(path: &Path) -> Result<LevelFormat, LoadError> {
         if path.is_dir() {
             return Ok(LevelFormat::Folder);
         }
@@ -343,72 +437,37 @@ impl GamePlayState {
                     return Ok(LevelFormat::RBStorage);
                 }
             }
-            return Ok(LevelFormat::SingleGrid);
+            return Ok(LevelFormat::SingleGrid(PageFormat::from_path(path)));
         }
         if !path.exists() {
-            return Err(::std::io::Error::new(
-                ::std::io::ErrorKind::NotFound,
-                format!("Does not exist {:?}", path),
+            return Err(LoadError::io(
+                path,
+                ::std::io::Error::new(::std::io::ErrorKind::NotFound, "Does not exist"),
             ));
         }
-        eprintln!("Unrecognized file format.");
-        return Err(::std::io::Error::new(
-            ::std::io::ErrorKind::InvalidData,
-            "Unrecognized level format",
-        ));
+        return Err(LoadError::new(path, LoadErrorKind::UnrecognizedFormat));
     }
 
-    pub fn load_from_folder(path: &Path) -> std::io::Result<GamePlayState> {
-        //let docs = ::yaml_rust::YamlLoader::
-        let config_path = path.join("config.yaml");
-        let level_config = if config_path.exists() {
-            LevelConfig::load(&config_path.to_path_buf())?
-        } else {
-            LevelConfig::new()
-        };
-        let encoding = Encoding::get_encoding(&level_config.encoding)?;
+    pub fn load_from_folder(path: &Path) -> Result<GamePlayState, LoadError> {
+        let level_config = LevelConfig::load_folder(path)?;
+        let level_pages = LevelPages::load_with_config(path, &level_config)?;
         let mut game_state = GamePlayState::new();
         game_state.game_rules = level_config.rules;
-
-        //pages in yaml
-        for page_config in &level_config.page_descr {
-            let file_name = if let Some(name) = &page_config.file_name {
-                name.clone()
-            } else {
-                let name = format!("{}.pdiff", page_config.id);
-                if path.join(&name).exists() {
-                    name
-                } else {
-                    format!("{}.txt", page_config.id)
-                }
-            };
-            //TODO: finish implementing pdiff support
-            let byte_grid = ByteGrid::load(&path.join(file_name), &encoding)?;
-            let mut page_state = PageState::from_grid(byte_grid);
-            for trigger in &page_config.extra_triggers {
+        game_state.info = level_config.info;
+
+        for PageFile { id, grid, .. } in level_pages.pages {
+            let mut page_state = PageState::from_grid(grid);
+            let page_config = level_config
+                .page_descr
+                .iter()
+                .filter(|descr| descr.id == id);
+            for trigger in page_config.flat_map(|descr| descr.extra_triggers.iter()) {
                 page_state.triggers.insert(
                     joinu8(trigger.pos.x as u8, trigger.pos.y as u8),
                     trigger.clone(),
                 );
             }
-            game_state.pages.insert(page_config.id, page_state);
-        }
-
-        // rest of the pages named number.txt
-        for file in path.iter() {
-            let path = path.join(file);
-            if !path.is_file() {
-                continue;
-            }
-            let name = file.to_str().unwrap_or("not");
-            if let Ok(number) = name.parse::<u8>() {
-                if !game_state.pages.contains_key(&number) {
-                    let byte_grid = ByteGrid::load(&path, &encoding)?;
-                    game_state
-                        .pages
-                        .insert(number, PageState::from_grid(byte_grid));
-                }
-            }
+            game_state.pages.insert(id, page_state);
         }
 
         if let Some(page_id) = level_config.initial_page {
@@ -423,6 +482,26 @@ impl GamePlayState {
             game_state.set_initial_page(DEFAULT_PAGE);
         }
 
+        if let Some(visited) = &level_config.visited_pages {
+            game_state.visited_pages = game_state.visited_pages.union(visited);
+        }
+
+        let mut player_register = None;
+        if let Some(cpu) = &level_config.cpu {
+            game_state.cpu[0].pc = cpu.pc;
+            let registers = game_state.cpu[0].registers.iter_mut();
+            for (register, value) in registers.zip(&cpu.registers) {
+                register.value = *value;
+            }
+            player_register = cpu
+                .player_register
+                .filter(|r| *r < game_state.cpu[0].registers.len());
+        }
+        if let Some(r) = player_register {
+            game_state.player = PlayerPos::Register(r);
+            return Ok(game_state);
+        }
+
         let initial_pos = if let Some(pos) = level_config.initial_pos {
             pos
         } else {
@@ -439,49 +518,90 @@ impl GamePlayState {
         Ok(game_state)
     }
 
-    pub fn load_from_rbstorage(path: &Path) -> std::io::Result<GamePlayState> {
-        eprintln!("Loading .storage file");
+    /// Write current pages, triggers, player placement and CPU state as folder level. Pages are
+    /// stored as `.bin` files named by hex page id.
+    pub fn save_to_folder(&self, path: &Path) -> Result<(), LoadError> {
+        std::fs::create_dir_all(path).map_err(|e| LoadError::io(path, e))?;
+        let mut level_config = LevelConfig::new();
+        level_config.initial_page = Some(self.player_page);
+        let mut player_register = None;
+        match self.player {
+            PlayerPos::Pos(pos) => level_config.initial_pos = Some(pos),
+            PlayerPos::Register(r) => player_register = Some(r),
+        }
+        level_config.cpu = Some(CpuConfig {
+            pc: self.cpu[0].pc,
+            registers: self.cpu[0].registers.iter().map(|r| r.value).collect(),
+            player_register,
+        });
+        level_config.rules = self.game_rules.clone();
+        level_config.visited_pages = Some(self.visited_pages.clone());
+        level_config.info = self.info.clone();
+
+        let mut ids: Vec<u8> = self.pages.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let page = &self.pages[&id];
+            let file_name = format!("{:02x}.bin", id);
+            let page_path = path.join(&file_name);
+            std::fs::write(&page_path, page.memory.to_bytes(GridOrder::RowMajor))
+                .map_err(|e| LoadError::io(&page_path, e))?;
+            let mut triggers: Vec<Trigger> = page.triggers.values().cloned().collect();
+            triggers.sort_by_key(|trigger| joinu16(trigger.pos));
+            level_config.page_descr.push(PageDescr {
+                extra_triggers: triggers,
+                id,
+                base_name: None,
+                file_name: Some(file_name),
+                order: GridOrder::RowMajor,
+            });
+        }
+
+        let config_path = path.join("config.yaml");
+        let file =
+            std::fs::File::create(&config_path).map_err(|e| LoadError::io(&config_path, e))?;
+        serde_yaml::to_writer(file, &level_config)
+            .map_err(|e| LoadError::new(&config_path, LoadErrorKind::Config(e.to_string())))
+    }
+
+    pub fn load_from_rbstorage(path: &Path) -> Result<GamePlayState, LoadError> {
         if !path.is_file() {
-            eprintln!("File {} does not exist", path.to_string_lossy());
-            return Err(std::io::Error::new(
-                ErrorKind::NotFound,
-                "Level file not found",
+            return Err(LoadError::io(
+                path,
+                std::io::Error::new(ErrorKind::NotFound, "Level file not found"),
             ));
         }
-        let handle_io_error = |e| {
-            eprintln!("{}", e);
-            e
-        };
+        let handle_io_error = |e| LoadError::io(path, e);
         let mut f = std::fs::File::open(path).map_err(handle_io_error)?;
         let mut buffer = Vec::new();
         f.read_to_end(&mut buffer).map_err(handle_io_error)?;
-        let rb_save: RBSave = crate::serde_rbbin::from_bytes(&buffer).map_err(|e| {
-            eprintln!("Failed to parse file {}", e);
-            std::io::Error::new(ErrorKind::InvalidData, "Failed to parse RB save")
-        })?;
+        let rb_save: RBSave = crate::serde_rbbin::from_bytes(&buffer)
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Save(e.to_string())))?;
         let mut game_state = GamePlayState::new();
 
-        let swap_bytes = |v: u16| (v >> 8) | (v << 8);
-
-        let page_from_map = |map: std::collections::HashMap<u16, u8>| {
+        let page_from_map = |map: &BTreeMap<u16, u8>| {
             let mut byte_grid = ByteGrid::new();
             for (key, value) in map {
-                byte_grid[swap_bytes(key)] = value;
+                byte_grid[key.swap_bytes()] = *value;
             }
             PageState::from_grid_raw(byte_grid)
         };
-        game_state.pages.insert(2, page_from_map(rb_save.realm2));
-        game_state.pages.insert(66, page_from_map(rb_save.realm42));
+        for (id, realm) in &rb_save.realms {
+            game_state.pages.insert(*id, page_from_map(realm));
+        }
+        game_state.game_rules.page_instruction = rb_save.allow_page_instruction != 0;
+        game_state.rb_player_location = rb_save.player_location;
+        game_state.rb_camera_bottom = rb_save.camera_bottom;
 
         for (id, page) in &mut game_state.pages {
             for trigger in &rb_save.jumps {
                 if trigger.realm != *id {
                     continue;
                 }
-                let effect = if trigger.code != 0 {
-                    TriggerKind::SetPC(trigger.code)
-                } else {
-                    TriggerKind::Message(trigger.achievement.clone())
+                let effect = match (trigger.code, trigger.achievement.as_str()) {
+                    (0, RB_WIN_ACHIEVEMENT) => TriggerKind::EndOfLevel,
+                    (0, achievement) => TriggerKind::Message(achievement.to_owned()),
+                    (code, _) => TriggerKind::SetPC(code),
                 };
                 page.triggers.insert(
                     joinu8(trigger.x, trigger.y),
@@ -495,7 +615,7 @@ impl GamePlayState {
             }
         }
 
-        game_state.set_initial_page(rb_save.page_register);
+        game_state.set_initial_page(rb_save.player_page);
 
         game_state.player =
             PlayerPos::Pos(V2::make(rb_save.player_x as i32, rb_save.player_y as i32));
@@ -505,28 +625,173 @@ impl GamePlayState {
             cpu.set_register(RegisterId::Page, rb_save.page_register);
             cpu.set_register(RegisterId::Data, rb_save.data_register);
 
-            cpu.pc = swap_bytes(rb_save.program_location) + rb_save.program_line * 0x100;
+            cpu.pc = rb_save.program_location.swap_bytes() + rb_save.program_line * 0x100;
+        }
+
+        let warnings = &mut game_state.load_warnings;
+        if !rb_save.unknown_fields.is_empty() {
+            warnings.push(format!(
+                "ignored unknown fields {}",
+                rb_save.unknown_fields.join(", ")
+            ));
+        }
+        if !rb_save.missing_fields.is_empty() {
+            warnings.push(format!(
+                "missing fields {} set to 0",
+                rb_save.missing_fields.join(", ")
+            ));
+        }
+        if !game_state.pages.contains_key(&rb_save.player_page) {
+            warnings.push(format!(
+                "no realm for player page {:x}",
+                rb_save.player_page
+            ));
         }
 
         Ok(game_state)
     }
 
-    pub fn load_from_path(path: &Path) -> std::io::Result<GamePlayState> {
+    /// Convert to Rogue Bit save. Pages become `realmN` maps with the same byte swap as on
+    /// import, used one time triggers are left out. Jumps to pc 0 and repeating triggers can't be
+    /// represented.
+    pub fn to_rb_save(&self) -> Result<RBSave, String> {
+        let pos = match self.player {
+            PlayerPos::Pos(pos) => pos,
+            PlayerPos::Register(_) => {
+                return Err("can't export while player is inside a register".to_owned())
+            }
+        };
+        let mut rb_save = RBSave::default();
+        let mut ids: Vec<u8> = self.pages.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let page = &self.pages[&id];
+            let realm = page
+                .memory
+                .iter()
+                .filter(|(_, value)| **value != 0)
+                .map(|((x, y), value)| (joinu8(x, y).swap_bytes(), *value))
+                .collect();
+            rb_save.realms.insert(id, realm);
+
+            let mut triggers: Vec<&Trigger> = page
+                .triggers
+                .values()
+                .filter(|trigger| trigger.is_active())
+                .collect();
+            triggers.sort_by_key(|trigger| joinu16(trigger.pos));
+            for trigger in triggers {
+                // RB jumps always fire once
+                if !trigger.one_time {
+                    return Err(format!(
+                        "repeating trigger at {},{} on page {:02x} can't be exported",
+                        trigger.pos.x, trigger.pos.y, id
+                    ));
+                }
+                let (code, achievement) = match &trigger.effect {
+                    // code 0 means achievement jump in RB saves
+                    TriggerKind::SetPC(0) => {
+                        return Err(format!(
+                            "jump to pc 0 at {},{} on page {:02x} can't be exported",
+                            trigger.pos.x, trigger.pos.y, id
+                        ))
+                    }
+                    TriggerKind::SetPC(code) => (*code, String::new()),
+                    TriggerKind::Message(m) => (0, m.clone()),
+                    TriggerKind::EndOfLevel => (0, RB_WIN_ACHIEVEMENT.to_owned()),
+                };
+                rb_save.jumps.push(RBJump {
+                    achievement,
+                    code,
+                    realm: id,
+                    x: trigger.pos.x as u8,
+                    y: trigger.pos.y as u8,
+                });
+            }
+        }
+
+        rb_save.allow_page_instruction = self.game_rules.page_instruction as u8;
+        rb_save.player_page = self.player_page;
+        rb_save.player_x = pos.x as u8;
+        rb_save.player_y = pos.y as u8;
+        rb_save.player_location = self.rb_player_location;
+        rb_save.camera_bottom = self.rb_camera_bottom;
+        let cpu = &self.cpu[0];
+        rb_save.compare_register = cpu.get_register(RegisterId::Compare).value;
+        rb_save.data_register = cpu.get_register(RegisterId::Data).value;
+        rb_save.page_register = cpu.get_register(RegisterId::Page).value;
+        rb_save.program_line = cpu.pc >> 8;
+        rb_save.program_location = (cpu.pc & 0xff).swap_bytes();
+        Ok(rb_save)
+    }
+
+    /// Write game as Rogue Bit `.storage` file.
+    pub fn save_rbstorage(&self, path: &Path) -> Result<(), LoadError> {
+        let rb_save = self
+            .to_rb_save()
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Save(e)))?;
+        let data = crate::serde_rbbin::to_bytes(&rb_save)
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Save(e.to_string())))?;
+        std::fs::write(path, data).map_err(|e| LoadError::io(path, e))
+    }
+
+    pub fn load_from_path(path: &Path) -> Result<GamePlayState, LoadError> {
         let level_format = GamePlayState::detect_level_format(path)?;
 
         match level_format {
-            LevelFormat::SingleGrid => GamePlayState::single_from_path(path),
+            LevelFormat::SingleGrid(_) => GamePlayState::single_from_path(path),
             LevelFormat::Folder => GamePlayState::load_from_folder(path),
             LevelFormat::RBStorage => GamePlayState::load_from_rbstorage(path),
         }
     }
 
+    /// FNV-1a hash of pages, triggers, player position, rules and CPU state, same level gives
+    /// same hash regardless of file format.
+    pub fn level_hash(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut add = |bytes: &[u8]| {
+            for byte in bytes {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        let mut ids: Vec<u8> = self.pages.keys().cloned().collect();
+        ids.sort();
+        for id in ids {
+            let page = &self.pages[&id];
+            add(&[id]);
+            for (_, v) in page.memory.iter() {
+                add(&[*v]);
+            }
+            let mut triggers: Vec<&Trigger> = page.triggers.values().collect();
+            triggers.sort_by_key(|t| (t.pos.y, t.pos.x));
+            for trigger in triggers {
+                add(&[trigger.pos.x as u8, trigger.pos.y as u8]);
+                add(trigger.effect_text().as_bytes());
+            }
+        }
+        add(&[self.player_page]);
+        match self.player {
+            PlayerPos::Pos(p) => add(&[0, p.x as u8, p.y as u8]),
+            PlayerPos::Register(r) => add(&[1, r as u8]),
+        }
+        add(&serde_json::to_vec(&self.game_rules).unwrap_or_default());
+        let cpu = &self.cpu[0];
+        add(&[(cpu.pc >> 8) as u8, cpu.pc as u8]);
+        for register in &cpu.registers {
+            add(&[register.value]);
+        }
+        hash
+    }
+
     pub fn accessible(&self, p: u8) -> bool {
         return (p & (self.player_mask())) == 0;
     }
 
     pub fn current_page(&self) -> &PageState {
-        let page_id = self.player_page;
+        self.page(self.player_page)
+    }
+
+    pub fn page(&self, page_id: u8) -> &PageState {
         self.pages.get(&page_id).unwrap_or(&self.null_page)
     }
 
@@ -581,23 +846,78 @@ impl GamePlayState {
         };
     }
 
-    fn rotate_page(&mut self) -> bool {
+    fn page_change_allowed(&self) -> bool {
         if !(self.game_rules.rotate_page == PageRotationRule::Always
             || (self.game_rules.rotate_page == PageRotationRule::AfterPageInstruction
                 && self.page_instruction_executed))
         {
             return false;
         }
+        // allow page rotate only in memory
         if let PlayerPos::Pos(_) = self.player {
+            true
         } else {
-            return false; // allow page rotate only in memory
+            false
         }
-        for i in 1u8..=255u8 {
-            let target_page = self.player_page.wrapping_add(i);
-            if self.visited_pages.get(target_page) {
-                self.player_page = target_page;
-                return false;
+    }
+
+    pub fn can_jump_to_page(&self, page: u8) -> bool {
+        self.page_change_allowed() && self.visited_pages.get(page)
+    }
+
+    /// Two instructions at consecutive pc values, checks only opcode bytes.
+    fn has_code(&self, page_id: u8) -> bool {
+        let page = match self.pages.get(&page_id) {
+            Some(page) => page,
+            None => return false,
+        };
+        let mut previous = false;
+        for pc in 0..=0xffffu16 {
+            let current = Instruction::is_opcode(self.effective_value(page, splitu16(pc)));
+            if previous && current {
+                return true;
             }
+            previous = current;
+        }
+        false
+    }
+
+    /// All loaded pages ordered by id.
+    pub fn page_list(&self) -> Vec<PageInfo> {
+        let player_mask = self.player_mask();
+        let mut ids: Vec<u8> = self.pages.keys().cloned().collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| PageInfo {
+                id,
+                visited: self.visited_pages.get(id),
+                cpu: self.cpu.iter().any(|cpu| {
+                    cpu.get_register_effective_r(RegisterId::Page, self.player, player_mask) == id
+                }),
+                has_code: self.has_code(id),
+                reachable: self.can_jump_to_page(id),
+            })
+            .collect()
+    }
+
+    fn jump_to_page(&mut self, page: u8) -> bool {
+        if self.can_jump_to_page(page) {
+            self.player_page = page;
+        }
+        false
+    }
+
+    fn rotate_page(&mut self) -> bool {
+        if !self.page_change_allowed() {
+            return false;
+        }
+        let next_page = self
+            .visited_pages
+            .iter()
+            .find(|page| *page > self.player_page)
+            .or_else(|| self.visited_pages.iter().next());
+        if let Some(page) = next_page {
+            self.player_page = page;
         }
         false
     }
@@ -640,8 +960,12 @@ impl GamePlayState {
         let advance_world = match action {
             PlayerMove::Move(dir) => self.move_player(dir),
             PlayerMove::RotatePage => self.rotate_page(),
+            PlayerMove::JumpToPage(page) => self.jump_to_page(page),
         };
         self.apply_triggers();
+        if advance_world {
+            self.moves += 1;
+        }
         if advance_world
             && self.player_page
                 == self.cpu[0].get_register_effective_r(
@@ -656,6 +980,7 @@ impl GamePlayState {
     }
 
     fn step_cpu(&mut self, id: usize) {
+        self.cpu_steps += 1;
         let player_mask = self.player_mask();
         let cpu = &mut self.cpu[id];
         let page_id = cpu.get_register_effective_r(RegisterId::Page, self.player, player_mask);
@@ -753,41 +1078,19 @@ impl GamePlayState {
         let page = self.pages.get(&page_id).unwrap_or(&self.null_page);
         let p = splitu16(pc);
         let instr = self.effective_value(page, p);
-        let arg_u8 = || {
-            let a0 = p + V2::make(1, 0);
-            if a0.x < 256 {
-                self.effective_value(page, a0)
-            } else {
-                0
-            }
-        };
-        let arg_u16 = || {
-            let a0 = p + V2::make(1, 0);
-            if a0.x < 256 {
-                let high = self.effective_value(page, a0);
-                let a1 = a0 + V2::make(1, 0);
-                let low = if a1.x < 256 {
-                    self.effective_value(page, a1)
-                } else {
-                    0
-                };
-                ((high as u16) << 8) | (low as u16)
+        let arg = |offset: i32| {
+            let a = p + V2::make(offset, 0);
+            if a.x < 256 {
+                self.effective_value(page, a)
             } else {
                 0
             }
         };
+        Instruction::decode(instr, ((arg(1) as u16) << 8) | (arg(2) as u16))
+    }
 
-        match instr {
-            b'j' => Instruction::Jump(arg_u16()),
-            b's' => Instruction::Swap(arg_u16()),
-            b'c' => Instruction::Compare(arg_u8()),
-            b'e' => Instruction::JumpEqual(arg_u16()),
-            b'l' => Instruction::JumpLess(arg_u16()),
-            b'g' => Instruction::JumpGreater(arg_u16()),
-            b'a' => Instruction::Add(arg_u8()),
-            b'p' => Instruction::Page(arg_u8()),
-            _ => Instruction::None,
-        }
+    pub fn visited_pages(&self) -> &Bits256 {
+        &self.visited_pages
     }
 
     fn change_player_page(&mut self, page: u8) {
@@ -801,13 +1104,184 @@ impl GamePlayState {
     }
 }
 
+/// Page of folder level as stored on disk.
+pub struct PageFile {
+    pub id: u8,
+    pub path: PathBuf,
+    pub format: PageFormat,
+    pub grid: ByteGrid,
+    /// Extra triggers from level config.
+    pub triggers: Vec<Trigger>,
+}
+
+/// Pages of folder level without triggers and player placement applied.
+pub struct LevelPages {
+    pub encoding: Encoding,
+    pub pages: Vec<PageFile>,
+}
+
+impl LevelPages {
+    pub fn load(path: &Path) -> Result<LevelPages, LoadError> {
+        let level_config = LevelConfig::load_folder(path)?;
+        LevelPages::load_with_config(path, &level_config)
+    }
+
+    fn load_with_config(path: &Path, level_config: &LevelConfig) -> Result<LevelPages, LoadError> {
+        let encoding = Encoding::get_encoding(&level_config.encoding)
+            .map_err(|e| LoadError::io(&path.join("config.yaml"), e))?;
+        let mut pages = Vec::new();
+
+        //pages in yaml
+        for page_config in &level_config.page_descr {
+            let file_name = if let Some(name) = &page_config.file_name {
+                name.clone()
+            } else {
+                ["pdiff", "bin", "hex"]
+                    .iter()
+                    .map(|ext| format!("{}.{}", page_config.id, ext))
+                    .find(|name| path.join(name).exists())
+                    .unwrap_or_else(|| format!("{}.txt", page_config.id))
+            };
+            //TODO: finish implementing pdiff support
+            let page_path = path.join(file_name);
+            let format = page_config.format(&page_path);
+            let grid = ByteGrid::load_format(&page_path, format, &encoding, level_config.strict)?;
+            pages.push(PageFile {
+                id: page_config.id,
+                path: page_path,
+                format,
+                grid,
+                triggers: page_config.extra_triggers.clone(),
+            });
+        }
+
+        // rest of the pages named number.txt
+        for file in path.iter() {
+            let path = path.join(file);
+            if !path.is_file() {
+                continue;
+            }
+            let name = file.to_str().unwrap_or("not");
+            if let Ok(number) = name.parse::<u8>() {
+                if !pages.iter().any(|page| page.id == number) {
+                    let grid = ByteGrid::load_text(&path, &encoding, level_config.strict)?;
+                    pages.push(PageFile {
+                        id: number,
+                        path,
+                        format: PageFormat::Text,
+                        grid,
+                        triggers: Vec::new(),
+                    });
+                }
+            }
+        }
+        Ok(LevelPages { encoding, pages })
+    }
+
+    pub fn get(&self, id: u8) -> Option<&PageFile> {
+        self.pages.iter().rev().find(|page| page.id == id)
+    }
+
+    /// Write config of level at `path` to `output` folder with extra triggers of `page`
+    /// replaced.
+    pub fn save_triggers(
+        path: &Path,
+        output: &Path,
+        page: &PageFile,
+        triggers: Vec<Trigger>,
+    ) -> Result<(), LoadError> {
+        let mut level_config = LevelConfig::load_folder(path)?;
+        level_config.page_descr.retain(|descr| descr.id != page.id);
+        level_config.page_descr.push(PageDescr {
+            extra_triggers: triggers,
+            id: page.id,
+            base_name: None,
+            file_name: page
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            order: match page.format {
+                PageFormat::Binary(order) => order,
+                _ => GridOrder::default(),
+            },
+        });
+        let config_path = output.join("config.yaml");
+        let file =
+            std::fs::File::create(&config_path).map_err(|e| LoadError::io(&config_path, e))?;
+        serde_yaml::to_writer(file, &level_config)
+            .map_err(|e| LoadError::new(&config_path, LoadErrorKind::Config(e.to_string())))
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct PageDescr {
     #[serde(default)]
     extra_triggers: Vec<Trigger>,
     id: u8,
     base_name: Option<String>,
+    /// Page file: encoded text, `.bin` raw binary or `.hex` hexdump.
     file_name: Option<String>,
+    /// Cell order for `.bin` pages.
+    #[serde(default)]
+    order: GridOrder,
+}
+
+impl PageDescr {
+    fn format(&self, path: &Path) -> PageFormat {
+        match PageFormat::from_path(path) {
+            PageFormat::Binary(_) => PageFormat::Binary(self.order),
+            format => format,
+        }
+    }
+}
+
+/// Descriptive part of level config, all fields optional.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+pub struct LevelInfo {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Shown before level starts.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    /// Number of moves level author considers good.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub par_moves: Option<u32>,
+}
+
+impl LevelInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == LevelInfo::default()
+    }
+
+    /// "title by author" using whichever parts are present.
+    pub fn heading(&self) -> Option<String> {
+        match (&self.title, &self.author) {
+            (Some(title), Some(author)) => Some(format!("{} by {}", title, author)),
+            (Some(title), None) => Some(title.clone()),
+            (None, Some(author)) => Some(format!("by {}", author)),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CpuConfig {
+    pc: u16,
+    /// Register values in `RegisterId` order.
+    registers: Vec<u8>,
+    /// Register holding the player, player starts on a page when not given.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player_register: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -822,6 +1296,19 @@ struct LevelConfig {
     encoding: String,
     #[serde(default)]
     page_descr: Vec<PageDescr>,
+    /// Reject pages with overlong lines or too many lines instead of truncating them.
+    #[serde(default)]
+    strict: bool,
+    /// Pages available for rotation in addition to the initial page.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visited_pages: Option<Bits256>,
+    /// CPU state of a saved game, pc 0 and default registers when not given.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cpu: Option<CpuConfig>,
+    #[serde(flatten)]
+    info: LevelInfo,
 }
 
 impl LevelConfig {
@@ -832,6 +1319,10 @@ impl LevelConfig {
             rules: GameRules::new(),
             encoding: "437".to_owned(),
             page_descr: Vec::new(),
+            strict: false,
+            visited_pages: None,
+            cpu: None,
+            info: LevelInfo::default(),
         }
     }
 
@@ -839,17 +1330,20 @@ impl LevelConfig {
         "437".to_owned()
     }
 
-    fn load(path: &Path) -> std::io::Result<LevelConfig> {
-        let file = std::fs::File::open(path)?;
-        let y: serde_yaml::Result<LevelConfig> = ::serde_yaml::from_reader(file);
-        match y {
-            Ok(res) => Ok(res),
-            Err(e) => {
-                eprintln!("Level loading error: {}", e);
-                Err(Error::new(ErrorKind::InvalidData, e))
-            }
+    fn load_folder(path: &Path) -> Result<LevelConfig, LoadError> {
+        let config_path = path.join("config.yaml");
+        if config_path.exists() {
+            LevelConfig::load(&config_path)
+        } else {
+            Ok(LevelConfig::new())
         }
     }
+
+    fn load(path: &Path) -> Result<LevelConfig, LoadError> {
+        let file = std::fs::File::open(path).map_err(|e| LoadError::io(path, e))?;
+        let y: serde_yaml::Result<LevelConfig> = ::serde_yaml::from_reader(file);
+        y.map_err(|e| LoadError::new(path, LoadErrorKind::Config(e.to_string())))
+    }
 }
 
 #[derive(PartialEq, Eq)]
@@ -866,6 +1360,28 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// Instruction with opcode `op` and the two bytes following it as `arg`. Single byte
+    /// operands take the high byte.
+    pub fn decode(op: u8, arg: u16) -> Instruction {
+        let arg_u8 = (arg >> 8) as u8;
+        match op {
+            b'j' => Instruction::Jump(arg),
+            b's' => Instruction::Swap(arg),
+            b'c' => Instruction::Compare(arg_u8),
+            b'e' => Instruction::JumpEqual(arg),
+            b'l' => Instruction::JumpLess(arg),
+            b'g' => Instruction::JumpGreater(arg),
+            b'a' => Instruction::Add(arg_u8),
+            b'p' => Instruction::Page(arg_u8),
+            _ => Instruction::None,
+        }
+    }
+
+    /// Byte that decodes to something other than `None`.
+    pub fn is_opcode(v: u8) -> bool {
+        Instruction::decode(v, 0) != Instruction::None
+    }
+
     pub fn mem_operand(&self) -> Option<u16> {
         match self {
             Instruction::Swap(v)
@@ -950,6 +1466,7 @@ impl CPU {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::resource::TempPath;
 
     #[test]
     fn add_pos_normal() {
@@ -1173,4 +1690,227 @@ mod tests {
 
         //TODO: add test for rotate in register/page
     }
+
+    #[test]
+    fn page_list() {
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"@"));
+        game.pages
+            .insert(0x02, PageState::from_grid(ByteGrid::from_raw_str(b"c\np")));
+        game.pages
+            .insert(0x50, PageState::from_grid(ByteGrid::from_raw_str(b"cp")));
+        game.visited_pages.set(0x02, true);
+        let list = game.page_list();
+        assert_eq!(
+            list[0],
+            PageInfo {
+                id: 0x02,
+                visited: true,
+                cpu: false,
+                has_code: true,
+                reachable: true,
+            }
+        );
+        assert_eq!(
+            (list[1].id, list[1].cpu, list[1].has_code),
+            (0x42, true, false)
+        );
+        assert_eq!((list[2].has_code, list[2].reachable), (false, false));
+        for v in 0..=255u8 {
+            game.pages.get_mut(&0x50).unwrap().memory[(1, 0)] = v;
+            let decoded = game.read_instruction(0x0100, 0x50) != Instruction::None;
+            assert_eq!(Instruction::is_opcode(v), decoded);
+        }
+
+        game.make_move(PlayerMove::JumpToPage(0x50));
+        assert_eq!(game.player_page, 0x42);
+        game.make_move(PlayerMove::JumpToPage(0x02));
+        assert_eq!(game.player_page, 0x02);
+
+        game.game_rules.rotate_page = PageRotationRule::Never;
+        assert!(!game.can_jump_to_page(0x42));
+        game.make_move(PlayerMove::JumpToPage(0x42));
+        assert_eq!(game.player_page, 0x02);
+    }
+
+    #[test]
+    fn rb_save_round_trip() {
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"  \n @ x"));
+        game.pages.insert(
+            0x02,
+            PageState::from_grid_raw(ByteGrid::from_raw_str(b"ab")),
+        );
+        game.pages.get_mut(&DEFAULT_PAGE).map(|page| {
+            page.triggers.insert(
+                0x0300,
+                Trigger {
+                    pos: V2::make(3, 0),
+                    effect: TriggerKind::SetPC(0x1234),
+                    triggered: false,
+                    one_time: true,
+                },
+            );
+            page.triggers.insert(
+                0x0500,
+                Trigger {
+                    pos: V2::make(5, 0),
+                    effect: TriggerKind::EndOfLevel,
+                    triggered: false,
+                    one_time: true,
+                },
+            );
+        });
+        game.cpu[0].pc = 0x0105;
+        game.cpu[0].set_register(RegisterId::Data, 7);
+        game.rb_player_location = 12;
+        game.rb_camera_bottom = -3;
+
+        let path = TempPath::new("save.storage");
+        game.save_rbstorage(&path).unwrap();
+        let loaded = GamePlayState::load_from_rbstorage(&path).unwrap();
+        assert!(loaded.load_warnings.is_empty());
+        let exported = loaded.to_rb_save().unwrap();
+        assert_eq!((exported.player_location, exported.camera_bottom), (12, -3));
+        assert_eq!(loaded.player, PlayerPos::Pos(V2::make(1, 1)));
+        assert_eq!(loaded.player_page, DEFAULT_PAGE);
+        assert_eq!(loaded.cpu[0].pc, 0x0105);
+        assert_eq!(loaded.cpu[0].get_register(RegisterId::Data).value, 7);
+        assert!(loaded.page(2).memory == game.page(2).memory);
+        assert!(loaded.page(DEFAULT_PAGE).memory == game.page(DEFAULT_PAGE).memory);
+        let trigger = &loaded.page(DEFAULT_PAGE).triggers[&0x0300];
+        assert_eq!(trigger.pos, V2::make(3, 0));
+        let trigger = &loaded.page(DEFAULT_PAGE).triggers[&0x0500];
+        match trigger.effect {
+            TriggerKind::EndOfLevel => {}
+            _ => panic!("WIN jump not imported as end of level"),
+        }
+
+        game.player = PlayerPos::Register(0);
+        assert!(game.to_rb_save().is_err());
+
+        game.player = PlayerPos::Pos(V2::make(1, 1));
+        game.pages.get_mut(&DEFAULT_PAGE).unwrap().triggers.insert(
+            0x0400,
+            Trigger {
+                pos: V2::make(4, 0),
+                effect: TriggerKind::SetPC(0),
+                triggered: false,
+                one_time: true,
+            },
+        );
+        assert_eq!(
+            game.to_rb_save().err().unwrap(),
+            "jump to pc 0 at 4,0 on page 42 can't be exported"
+        );
+
+        let page = game.pages.get_mut(&DEFAULT_PAGE).unwrap();
+        page.triggers.remove(&0x0400);
+        page.triggers.get_mut(&0x0300).unwrap().one_time = false;
+        assert_eq!(
+            game.to_rb_save().err().unwrap(),
+            "repeating trigger at 3,0 on page 42 can't be exported"
+        );
+    }
+
+    #[test]
+    fn save_visited_pages() {
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"@ x"));
+        game.pages.insert(0x02, PageState::new());
+        game.pages.insert(0x50, PageState::new());
+        game.visited_pages.set(0x50, true);
+        game.visited_pages.set(0x02, true);
+        game.make_move(PlayerMove::Move(MoveDir::Right));
+        game.make_move(PlayerMove::RotatePage);
+        assert_eq!(game.player_page, 0x50);
+        game.make_move(PlayerMove::RotatePage);
+        assert_eq!(game.player_page, 0x02);
+        game.cpu[0].pc = 0x1234;
+        game.cpu[0].set_register(RegisterId::Compare, 0x17);
+
+        let path = TempPath::new("save");
+        game.save_to_folder(&path).unwrap();
+        assert!(path.join("50.bin").exists());
+        let loaded = GamePlayState::load_from_folder(&path).unwrap();
+        assert_eq!(loaded.player_page, 0x02);
+        assert_eq!(loaded.player, PlayerPos::Pos(V2::make(1, 0)));
+        assert_eq!(loaded.visited_pages().to_string(), "02,42,50");
+        assert_eq!(loaded.page(DEFAULT_PAGE).memory[(2, 0)], b'x');
+        assert_eq!(loaded.cpu[0].pc, 0x1234);
+        assert_eq!(loaded.cpu[0].get_register(RegisterId::Compare).value, 0x17);
+    }
+
+    #[test]
+    fn save_player_in_register() {
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"@ x"));
+        game.player = PlayerPos::Register(RegisterId::Page as usize);
+        game.cpu[0].set_register(RegisterId::Page, 0x42);
+
+        let path = TempPath::new("save_register");
+        game.save_to_folder(&path).unwrap();
+        let loaded = GamePlayState::load_from_folder(&path).unwrap();
+        assert_eq!(
+            loaded.player,
+            PlayerPos::Register(RegisterId::Page as usize)
+        );
+        assert_eq!(loaded.page(DEFAULT_PAGE).memory[(2, 0)], b'x');
+    }
+
+    #[test]
+    fn level_info() {
+        let config: LevelConfig = serde_yaml::from_str(
+            "initial_page: 0x02\n\
+             title: Bits\n\
+             author: someone\n\
+             description: |\n  Line one\n  Line two\n\
+             difficulty: hard\n\
+             par_moves: 120\n",
+        )
+        .unwrap();
+        assert_eq!(config.initial_page, Some(2));
+        assert_eq!(config.info.heading().unwrap(), "Bits by someone");
+        assert_eq!(
+            config.info.description.as_ref().unwrap(),
+            "Line one\nLine two\n"
+        );
+        assert_eq!(config.info.difficulty.as_ref().unwrap(), "hard");
+        assert_eq!(config.info.par_moves, Some(120));
+
+        let config: LevelConfig = serde_yaml::from_str("strict: true").unwrap();
+        assert!(config.info.is_empty());
+        assert!(config.info.heading().is_none());
+
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"@"));
+        game.info.title = Some("Saved".to_owned());
+        let path = TempPath::new("info");
+        game.save_to_folder(&path).unwrap();
+        let loaded = GamePlayState::load_from_folder(&path).unwrap();
+        assert_eq!(loaded.info, game.info);
+    }
+
+    #[test]
+    fn counters_and_hash() {
+        let level = b"@ \n x";
+        let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(level));
+        let hash = game.level_hash();
+        assert_eq!(
+            hash,
+            GamePlayState::from_grid(ByteGrid::from_raw_str(level)).level_hash()
+        );
+        assert_ne!(
+            hash,
+            GamePlayState::from_grid(ByteGrid::from_raw_str(b"@ \n y")).level_hash()
+        );
+        let mut other = GamePlayState::from_grid(ByteGrid::from_raw_str(level));
+        other.game_rules.page_instruction = false;
+        assert_ne!(hash, other.level_hash());
+        let mut other = GamePlayState::from_grid(ByteGrid::from_raw_str(level));
+        other.cpu[0].pc = 0x0100;
+        assert_ne!(hash, other.level_hash());
+
+        game.make_move(PlayerMove::Move(MoveDir::Right));
+        game.make_move(PlayerMove::Move(MoveDir::Down));
+        assert_eq!(game.player, PlayerPos::Pos(V2::make(1, 0)));
+        assert_eq!(game.moves, 2);
+        assert_eq!(game.cpu_steps, 2);
+        assert_ne!(game.level_hash(), hash);
+    }
 }
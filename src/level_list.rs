@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::gameplay::{GamePlayState, LevelFormat};
+
+pub struct LevelEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub format: LevelFormat,
+    /// Directory group the level was found in: "resource", "user" or "saves".
+    pub source: &'static str,
+}
+
+/// Folders and files in `dir` that look like levels, sorted by name. Missing directory
+/// gives empty list.
+pub fn scan_dir(dir: &Path, source: &'static str) -> Vec<LevelEntry> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut result: Vec<LevelEntry> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_dir()
+                || match path.extension().and_then(|ext| ext.to_str()) {
+                    Some("storage") | Some("txt") | Some("bin") | Some("hex") => true,
+                    _ => false,
+                }
+        })
+        .filter_map(|path| {
+            let format = GamePlayState::detect_level_format(&path).ok()?;
+            Some(LevelEntry {
+                name: path.file_name()?.to_string_lossy().into_owned(),
+                path,
+                format,
+                source,
+            })
+        })
+        .collect();
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
+/// Levels from resource `levels` directory followed by user levels and saves.
+pub fn find_levels() -> Vec<LevelEntry> {
+    let mut result = Vec::new();
+    if let Ok(dir) = crate::resource::get_resource_dir() {
+        result.extend(scan_dir(&dir.join("levels"), "resource"));
+    }
+    if let Ok(dir) = crate::resource::get_user_dir() {
+        result.extend(scan_dir(&dir.join("levels"), "user"));
+        result.extend(scan_dir(&dir.join("saves"), "saves"));
+    }
+    result
+}
+
+/// Latest modification time of file, or of folder and files directly in it.
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    let mut modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path).ok()?.filter_map(|entry| entry.ok()) {
+            if let Ok(time) = entry.metadata().and_then(|m| m.modified()) {
+                modified = std::cmp::max(modified, time);
+            }
+        }
+    }
+    Some(modified)
+}
+
+/// `GamePlayState::level_hash` of level files, reused until files change.
+#[derive(Default)]
+pub struct HashCache {
+    entries: HashMap<PathBuf, (SystemTime, Option<u64>)>,
+}
+
+impl HashCache {
+    /// Check modification times of `levels` and hash new or changed ones. Levels not in the
+    /// list are forgotten.
+    pub fn refresh(&mut self, levels: &[LevelEntry]) {
+        let mut entries = HashMap::new();
+        for level in levels {
+            let modified = match modified_time(&level.path) {
+                Some(modified) => modified,
+                None => continue,
+            };
+            let hash = match self.entries.remove(&level.path) {
+                Some((time, hash)) if time == modified => hash,
+                _ => GamePlayState::load_from_path(&level.path)
+                    .ok()
+                    .map(|game| game.level_hash()),
+            };
+            entries.insert(level.path.clone(), (modified, hash));
+        }
+        self.entries = entries;
+    }
+
+    /// Hash from last `refresh`, None if level didn't load.
+    pub fn get(&self, path: &Path) -> Option<u64> {
+        self.entries.get(path).and_then(|(_, hash)| *hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::TempPath;
+
+    #[test]
+    fn scan() {
+        let dir = TempPath::new("levels");
+        std::fs::create_dir_all(dir.join("pack")).unwrap();
+        for name in &["a.txt", "b.storage", "c.bin", "notes.md"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let levels = scan_dir(&dir, "user");
+        std::fs::remove_dir_all(&dir).unwrap();
+        let found: Vec<(&str, &str)> = levels
+            .iter()
+            .map(|level| (level.name.as_str(), level.format.name()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a.txt", "text"),
+                ("b.storage", "storage"),
+                ("c.bin", "binary"),
+                ("pack", "folder")
+            ]
+        );
+        assert!(scan_dir(&dir, "user").is_empty());
+    }
+
+    #[test]
+    fn hash_cache() {
+        let dir = TempPath::new("hashes");
+        std::fs::create_dir_all(&dir).unwrap();
+        let level = dir.join("a.txt");
+        std::fs::write(&level, b"@ x").unwrap();
+        let broken = dir.join("b.storage");
+        std::fs::write(&broken, b"").unwrap();
+
+        let levels = scan_dir(&dir, "user");
+        let mut cache = HashCache::default();
+        cache.refresh(&levels);
+        let hash = GamePlayState::load_from_path(&level).unwrap().level_hash();
+        assert_eq!(cache.get(&level), Some(hash));
+        assert_eq!(cache.get(&broken), None);
+
+        // unchanged file isn't loaded again
+        cache.entries.get_mut(&level).unwrap().1 = Some(1);
+        cache.refresh(&levels);
+        assert_eq!(cache.get(&level), Some(1));
+        // changed modification time reloads
+        cache.entries.get_mut(&level).unwrap().0 = SystemTime::UNIX_EPOCH;
+        assert_eq!(cache.get(&level), Some(1));
+        cache.refresh(&levels);
+        assert_eq!(cache.get(&level), Some(hash));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        cache.refresh(&levels);
+        assert_eq!(cache.get(&level), None);
+    }
+}
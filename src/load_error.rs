@@ -0,0 +1,118 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::bytegrid::N;
+
+#[derive(Debug)]
+pub enum LoadErrorKind {
+    Io(std::io::Error),
+    /// Character that has no mapping in the encoding. Line and column are 1-based.
+    UnknownChar {
+        line: usize,
+        column: usize,
+        c: char,
+    },
+    /// Line longer than 256 cells, only reported in strict mode.
+    LineTooLong {
+        line: usize,
+        length: usize,
+    },
+    /// File with more than 256 lines, only reported in strict mode.
+    TooManyLines(usize),
+    /// Raw binary page with wrong number of bytes.
+    InvalidSize {
+        expected: usize,
+        actual: usize,
+    },
+    /// Malformed hexdump line. Line and column are 1-based.
+    InvalidHex {
+        line: usize,
+        column: usize,
+    },
+    Config(String),
+    Region(String),
+    Save(String),
+    UnrecognizedFormat,
+}
+
+#[derive(Debug)]
+pub struct LoadError {
+    pub path: PathBuf,
+    pub kind: LoadErrorKind,
+}
+
+impl LoadError {
+    pub fn new(path: &Path, kind: LoadErrorKind) -> LoadError {
+        LoadError {
+            path: path.to_path_buf(),
+            kind,
+        }
+    }
+
+    pub fn io(path: &Path, error: std::io::Error) -> LoadError {
+        LoadError::new(path, LoadErrorKind::Io(error))
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self.kind {
+            LoadErrorKind::UnknownChar { line, .. }
+            | LoadErrorKind::LineTooLong { line, .. }
+            | LoadErrorKind::InvalidHex { line, .. } => Some(line),
+            _ => None,
+        }
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        match self.kind {
+            LoadErrorKind::UnknownChar { column, .. }
+            | LoadErrorKind::InvalidHex { column, .. } => Some(column),
+            LoadErrorKind::LineTooLong { .. } => Some(N + 1),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.to_string_lossy();
+        match &self.kind {
+            LoadErrorKind::Io(e) => write!(f, "{}: {}", path, e),
+            LoadErrorKind::UnknownChar { line, column, c } => write!(
+                f,
+                "{}:{}:{}: character '{}' (U+{:04X}) is not in the encoding",
+                path, line, column, c, *c as u32
+            ),
+            LoadErrorKind::LineTooLong { line, length } => write!(
+                f,
+                "{}:{}:{}: line has {} characters, at most {} allowed",
+                path,
+                line,
+                N + 1,
+                length,
+                N
+            ),
+            LoadErrorKind::TooManyLines(lines) => write!(
+                f,
+                "{}:{}: file has {} lines, at most {} allowed",
+                path,
+                N + 1,
+                lines,
+                N
+            ),
+            LoadErrorKind::InvalidSize { expected, actual } => write!(
+                f,
+                "{}: binary page has {} bytes, expected {}",
+                path, actual, expected
+            ),
+            LoadErrorKind::InvalidHex { line, column } => {
+                write!(f, "{}:{}:{}: invalid hexdump line", path, line, column)
+            }
+            LoadErrorKind::Config(msg) => write!(f, "{}: invalid level config: {}", path, msg),
+            LoadErrorKind::Region(msg) => write!(f, "{}: invalid region: {}", path, msg),
+            LoadErrorKind::Save(msg) => write!(f, "{}: failed to parse RB save: {}", path, msg),
+            LoadErrorKind::UnrecognizedFormat => write!(f, "{}: unrecognized level format", path),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
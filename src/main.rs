@@ -5,40 +5,181 @@ extern crate clap;
 #[macro_use]
 extern crate serde_derive;
 
+use std::collections::BTreeSet;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::Path;
 
 use clap::{App, Arg, ArgMatches};
 
-use crate::bytegrid::{ByteGrid, ByteGridDiff};
+use crate::bytegrid::{ByteGrid, ByteGridDiff, GridOrder, HunkCheck, MergeConflict, PageFormat};
+use crate::diff_report::DiffReport;
 use crate::encoding::Encoding;
 use crate::game_ui::*;
+use crate::gameplay::{LevelPages, PLAYER_OFFSET};
+use crate::netpbm::ImageFormat;
+use crate::patch::{PagePatch, Patch};
+use crate::region::Region;
 use tgame::ui::*;
+use tgame::vecmath::{Rectangle, V2};
 
+mod best_results;
 mod bytegrid;
+mod diff_report;
 mod encoding;
 mod game_ui;
 mod gameplay;
+mod level_list;
+mod load_error;
+mod netpbm;
+mod patch;
+mod region;
 mod resource;
+mod search;
 mod serde_rbbin;
+mod verify_import;
+
+fn page_format(path: &Path, args: &ArgMatches) -> PageFormat {
+    match PageFormat::from_path(path) {
+        PageFormat::Binary(_) if args.is_present("column_major") => {
+            PageFormat::Binary(GridOrder::ColumnMajor)
+        }
+        format => format,
+    }
+}
+
+fn load_page(path: &Path, args: &ArgMatches, encoding: &Encoding) -> Result<ByteGrid, ()> {
+    let format = page_format(path, args);
+    ByteGrid::load_format(path, format, encoding, args.is_present("strict")).map_err(|e| {
+        eprintln!("Could not load map {}", e);
+        ()
+    })
+}
+
+fn parse_page_id(value: &str) -> Result<u8, ()> {
+    let parsed = if value.starts_with("0x") {
+        u8::from_str_radix(&value[2..], 16)
+    } else {
+        value.parse::<u8>()
+    };
+    parsed.map_err(|_| {
+        eprintln!("Invalid page id {}", value);
+    })
+}
+
+fn load_level_pages(path: &Path) -> Result<LevelPages, ()> {
+    LevelPages::load(path).map_err(|e| {
+        eprintln!("Could not load level {}", e);
+    })
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy config and all pages of folder level so that modified pages can be written over the copy.
+/// Nothing is copied when both paths point to the same folder.
+fn copy_level(from: &Path, to: &Path) -> Result<(), ()> {
+    if let (Ok(from), Ok(to)) = (from.canonicalize(), to.canonicalize()) {
+        if from == to {
+            return Ok(());
+        }
+    }
+    copy_dir(from, to).map_err(|e| {
+        eprintln!("Could not copy level to {}: {}", to.display(), e);
+    })
+}
 
 fn run_diff(args: &ArgMatches) -> Result<(), ()> {
     let before_name = args.value_of("before").unwrap();
     let after_name = args.value_of("after").unwrap();
-    let encoding = Encoding::get_encoding("437").map_err(|e| {
-        eprintln!("Could not load encoding {:?}", e);
-        ()
-    })?;
-    let bytes_before = ByteGrid::load(Path::new(before_name), &encoding).map_err(|e| {
-        eprintln!("Could not load map {}: {:?}", before_name, e);
-        ()
-    })?;
-    let bytes_after = ByteGrid::load(Path::new(after_name), &encoding).map_err(|e| {
-        eprintln!("Could not load map {}: {:?}", after_name, e);
-        ()
-    })?;
-    let diff = bytes_before.diff(&bytes_after).serialize();
+    let before_path = Path::new(before_name);
+    let after_path = Path::new(after_name);
+    let format = args.value_of("format").unwrap();
+    let mut patch = Patch::new();
+    let mut report = DiffReport::new();
+    let diff = if before_path.is_dir() || after_path.is_dir() {
+        if !(before_path.is_dir() && after_path.is_dir()) {
+            eprintln!("Can't compare folder level with single page");
+            return Err(());
+        }
+        let before = load_level_pages(before_path)?;
+        let after = load_level_pages(after_path)?;
+        let ids: BTreeSet<u8> = after.pages.iter().map(|page| page.id).collect();
+        for id in ids {
+            let base = match before.get(id) {
+                Some(page) => &page.grid,
+                None => {
+                    eprintln!(
+                        "Warning: page {:02x} was added, it is not part of patch",
+                        id
+                    );
+                    continue;
+                }
+            };
+            let changed = &after.get(id).unwrap().grid;
+            if args.is_present("reversible") {
+                patch.add_page_reversible(id, base, changed);
+            } else {
+                patch.add_page(id, base, changed);
+            }
+            report.add_page(Some(id), base, changed, &after.encoding);
+        }
+        for page in &before.pages {
+            if after.get(page.id).is_none() {
+                eprintln!(
+                    "Warning: page {:02x} was removed, it is not part of patch",
+                    page.id
+                );
+            }
+        }
+        patch.serialize()
+    } else {
+        let encoding = Encoding::get_encoding("437").map_err(|e| {
+            eprintln!("Could not load encoding {:?}", e);
+            ()
+        })?;
+        let bytes_before = load_page(before_path, args, &encoding)?;
+        let bytes_after = load_page(after_path, args, &encoding)?;
+        if args.is_present("legacy") {
+            report.add_page(None, &bytes_before, &bytes_after, &encoding);
+            bytes_before.diff(&bytes_after).serialize()
+        } else {
+            let page = parse_page_id(args.value_of("page").unwrap())?;
+            if args.is_present("reversible") {
+                patch.add_page_reversible(page, &bytes_before, &bytes_after);
+            } else {
+                patch.add_page(page, &bytes_before, &bytes_after);
+            }
+            report.add_page(Some(page), &bytes_before, &bytes_after, &encoding);
+            patch.serialize()
+        }
+    };
+    let diff = match format {
+        "text" => {
+            let mut text = Vec::new();
+            report.write_text(&mut text).map_err(|_| ())?;
+            text
+        }
+        "json" => {
+            let mut json = serde_json::to_vec_pretty(&report).map_err(|e| {
+                eprintln!("Failed to serialize report {}", e);
+            })?;
+            json.push(b'\n');
+            json
+        }
+        _ => diff,
+    };
     if let Some(path) = args.value_of("output") {
         File::create(Path::new(path))
             .and_then(|mut out| out.write(&diff))
@@ -52,41 +193,412 @@ fn run_diff(args: &ArgMatches) -> Result<(), ()> {
     Ok(())
 }
 
+fn page_label(page_patch: &PagePatch) -> String {
+    page_patch
+        .page
+        .map_or("page".to_owned(), |id| format!("page {:02x}", id))
+}
+
+/// Diff undoing the patch when `--reverse` is given.
+fn reversed_diff(args: &ArgMatches, page_patch: &PagePatch) -> Result<Option<ByteGridDiff>, ()> {
+    if !args.is_present("reverse") {
+        return Ok(None);
+    }
+    page_patch.diff.reverse().map(Some).ok_or_else(|| {
+        eprintln!(
+            "Patch for {} has no old values, it can't be reversed",
+            page_label(page_patch)
+        );
+    })
+}
+
+/// Print how each hunk fits the page. Returns false if any cell conflicts.
+fn report_hunks(label: &str, checks: &[HunkCheck], encoding: &Encoding) -> bool {
+    let glyph = |v: u8| encoding.byte_to_char[v as usize];
+    for check in checks {
+        let (x, y) = (check.pos >> 8, check.pos & 0xff);
+        if check.conflicts.is_empty() {
+            println!(
+                "{}: hunk at {},{} ({} cells) applies cleanly",
+                label, x, y, check.cells
+            );
+            continue;
+        }
+        println!(
+            "{}: hunk at {},{} ({} cells) conflicts in {} cells",
+            label,
+            x,
+            y,
+            check.cells,
+            check.conflicts.len()
+        );
+        for conflict in &check.conflicts {
+            println!(
+                "  {},{}: expected {:02x} '{}', found {:02x} '{}'",
+                conflict.pos >> 8,
+                conflict.pos & 0xff,
+                conflict.expected,
+                glyph(conflict.expected),
+                conflict.actual,
+                glyph(conflict.actual)
+            );
+        }
+    }
+    checks.iter().all(|check| check.conflicts.is_empty())
+}
+
+/// Check that page matches what diff expects, cell by cell when it has old values and by
+/// base checksum otherwise. With `--dry-run` every hunk is reported.
+fn check_page_patch(
+    args: &ArgMatches,
+    page_patch: &PagePatch,
+    diff: &ByteGridDiff,
+    base: &ByteGrid,
+    encoding: &Encoding,
+) -> Result<(), ()> {
+    let label = page_label(page_patch);
+    let dry_run = args.is_present("dry_run");
+    let force = args.is_present("force") && !dry_run;
+    let clean = match diff.check(base) {
+        Some(checks) if dry_run => report_hunks(&label, &checks, encoding),
+        Some(checks) => {
+            let conflicts: usize = checks.iter().map(|check| check.conflicts.len()).sum();
+            if conflicts > 0 && !force {
+                eprintln!(
+                    "Patch for {} conflicts in {} cells, use --dry-run to list them or --force to apply anyway",
+                    label, conflicts
+                );
+            }
+            conflicts == 0
+        }
+        None => {
+            let matches = page_patch.matches_base(base);
+            if dry_run {
+                println!(
+                    "{}: no old values recorded, base checksum {}",
+                    label,
+                    if matches { "matches" } else { "differs" }
+                );
+            } else if !matches && !force {
+                eprintln!(
+                    "Patch for {} was made for different page contents, use --force to apply anyway",
+                    label
+                );
+            }
+            matches
+        }
+    };
+    if clean || force {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+fn patch_folder(args: &ArgMatches, level_path: &Path, patch: &Patch) -> Result<(), ()> {
+    let dry_run = args.is_present("dry_run");
+    let output_dir = match args.value_of("output") {
+        Some(output) => Path::new(output),
+        None if dry_run => Path::new(""),
+        None => {
+            eprintln!("Patching folder level requires output folder");
+            return Err(());
+        }
+    };
+    let level = load_level_pages(level_path)?;
+    let mut clean = true;
+    let mut patched = Vec::new();
+    for page_patch in &patch.pages {
+        let id = page_patch.page.ok_or_else(|| {
+            eprintln!("Patch without page ids can only be applied to single page");
+        })?;
+        let page = level.get(id).ok_or_else(|| {
+            eprintln!("Page {:02x} not found in level", id);
+        })?;
+        let reversed = reversed_diff(args, page_patch)?;
+        let diff = reversed.as_ref().unwrap_or(&page_patch.diff);
+        if check_page_patch(args, page_patch, diff, &page.grid, &level.encoding).is_err() {
+            clean = false;
+            continue;
+        }
+        let mut grid = page.grid.clone();
+        grid.patch(diff);
+        patched.push((page, grid));
+    }
+    if !clean {
+        return Err(());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    copy_level(level_path, output_dir)?;
+    for (page, grid) in patched {
+        let output_path = output_dir.join(page.path.strip_prefix(level_path).unwrap());
+        File::create(&output_path)
+            .and_then(|mut f| grid.save_format(&mut f, page.format, &level.encoding))
+            .map_err(|e| {
+                eprintln!("Write error: {}", e);
+            })?;
+    }
+    Ok(())
+}
+
 fn run_patch(args: &ArgMatches) -> Result<(), ()> {
     let before_name = args.value_of("data").unwrap();
     let patch = args.value_of("patch").unwrap();
-    let output = args.value_of("output");
-    let encoding = Encoding::get_encoding("437").map_err(|e| {
-        eprintln!("Could not load encoding {:?}", e);
-        ()
-    })?;
-    let mut bytes_before = ByteGrid::load(Path::new(before_name), &encoding).map_err(|e| {
-        eprintln!("Could not load map {}: {:?}", before_name, e);
-        ()
-    })?;
     let patch = std::fs::read(patch)
         .map_err(|e| {
             eprintln!("Could not read patch: {}", e);
             ()
         })
-        .and_then(|data| ByteGridDiff::deserialize(&data))
-        .map_err(|_| {
-            eprintln!("Could not decode patch");
-            ()
+        .and_then(|data| {
+            Patch::deserialize(&data).map_err(|e| {
+                eprintln!("Could not decode patch: {}", e);
+            })
         })?;
-    bytes_before.patch(&patch);
+    if Path::new(before_name).is_dir() {
+        return patch_folder(args, Path::new(before_name), &patch);
+    }
+
+    let page_patch = match args.value_of("page") {
+        Some(page) => {
+            let page = parse_page_id(page)?;
+            patch
+                .get_page(page)
+                .or_else(|| patch.pages.iter().find(|p| p.page.is_none()))
+        }
+        None if patch.pages.len() == 1 => patch.pages.first(),
+        None => {
+            eprintln!("Patch contains multiple pages, select one with --page");
+            return Err(());
+        }
+    }
+    .ok_or_else(|| {
+        eprintln!("Patch doesn't contain selected page");
+    })?;
+
+    let encoding = Encoding::get_encoding("437").map_err(|e| {
+        eprintln!("Could not load encoding {:?}", e);
+        ()
+    })?;
+    let mut bytes_before = load_page(Path::new(before_name), args, &encoding)?;
+    let reversed = reversed_diff(args, page_patch)?;
+    let diff = reversed.as_ref().unwrap_or(&page_patch.diff);
+    check_page_patch(args, page_patch, diff, &bytes_before, &encoding)?;
+    if args.is_present("dry_run") {
+        return Ok(());
+    }
+    bytes_before.patch(diff);
+    save_page(
+        &bytes_before,
+        args.value_of("output"),
+        before_name,
+        args,
+        &encoding,
+    )
+}
+
+fn save_page(
+    grid: &ByteGrid,
+    output: Option<&str>,
+    input_name: &str,
+    args: &ArgMatches,
+    encoding: &Encoding,
+) -> Result<(), ()> {
     let write_result = if let Some(output_path) = output {
-        File::create(Path::new(&output_path)).and_then(|mut f| bytes_before.save(&mut f, &encoding))
+        let format = page_format(Path::new(output_path), args);
+        File::create(Path::new(&output_path))
+            .and_then(|mut f| grid.save_format(&mut f, format, encoding))
     } else {
-        bytes_before.save(&mut std::io::stdout(), &encoding)
+        let format = page_format(Path::new(input_name), args);
+        grid.save_format(&mut std::io::stdout(), format, encoding)
     };
     write_result.map_err(|e| {
         eprintln!("Write error: {}", e);
-        return ();
+    })
+}
+
+fn run_merge(args: &ArgMatches) -> Result<(), ()> {
+    let encoding = Encoding::get_encoding("437").map_err(|e| {
+        eprintln!("Could not load encoding {:?}", e);
     })?;
+    let ours_name = args.value_of("ours").unwrap();
+    let base = load_page(Path::new(args.value_of("base").unwrap()), args, &encoding)?;
+    let ours = load_page(Path::new(ours_name), args, &encoding)?;
+    let theirs = load_page(Path::new(args.value_of("theirs").unwrap()), args, &encoding)?;
+
+    let (merged, conflicts) = ByteGrid::merge(&base, &ours, &theirs);
+    let glyph = |v: u8| encoding.byte_to_char[v as usize];
+    for conflict in &conflicts {
+        eprintln!(
+            "Conflict at {},{}: base {:02x} '{}', ours {:02x} '{}', theirs {:02x} '{}'",
+            conflict.x(),
+            conflict.y(),
+            conflict.base,
+            glyph(conflict.base),
+            conflict.ours,
+            glyph(conflict.ours),
+            conflict.theirs,
+            glyph(conflict.theirs)
+        );
+    }
+    save_page(&merged, args.value_of("output"), ours_name, args, &encoding)?;
+    if let Some(overlay_path) = args.value_of("conflicts") {
+        let overlay = MergeConflict::overlay(&conflicts);
+        save_page(&overlay, Some(overlay_path), ours_name, args, &encoding)?;
+    }
+    if !conflicts.is_empty() {
+        eprintln!("{} conflicting cells, kept our values", conflicts.len());
+        return Err(());
+    }
     Ok(())
 }
 
+/// Comma separated decimal numbers such as `x,y,width,height`.
+fn parse_numbers(value: &str, count: usize) -> Result<Vec<i32>, ()> {
+    let numbers: Vec<i32> = value
+        .split(',')
+        .map(|v| v.trim().parse::<i32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| {
+            eprintln!("Invalid number in {}", value);
+        })?;
+    if numbers.len() != count {
+        eprintln!("Expected {} comma separated numbers, got {}", count, value);
+        return Err(());
+    }
+    Ok(numbers)
+}
+
+fn plane_mask(args: &ArgMatches) -> u8 {
+    args.values_of("plane").map_or(0xff, |planes| {
+        planes.fold(0, |mask, plane| mask | 1 << plane.parse::<u8>().unwrap())
+    })
+}
+
+fn run_extract(args: &ArgMatches) -> Result<(), ()> {
+    let source = Path::new(args.value_of("source").unwrap());
+    let rect = parse_numbers(args.value_of("rect").unwrap(), 4)?;
+    let rect = Rectangle {
+        pos: V2::make(rect[0], rect[1]),
+        size: V2::make(rect[2], rect[3]),
+    };
+    let mask = plane_mask(args);
+    let region = if source.is_dir() {
+        let level = load_level_pages(source)?;
+        let id = parse_page_id(args.value_of("page").unwrap())?;
+        let page = level.get(id).ok_or_else(|| {
+            eprintln!("Page {:02x} not found in level", id);
+        })?;
+        Region::extract(&page.grid, &rect, mask, &page.triggers)
+    } else {
+        let encoding = Encoding::get_encoding("437").map_err(|e| {
+            eprintln!("Could not load encoding {:?}", e);
+        })?;
+        let grid = load_page(source, args, &encoding)?;
+        Region::extract(&grid, &rect, mask, &[])
+    };
+    if region.width == 0 || region.height == 0 {
+        eprintln!("Region is outside the page");
+        return Err(());
+    }
+    let output = args.value_of("output").unwrap();
+    File::create(Path::new(output))
+        .and_then(|mut f| region.save(&mut f))
+        .map_err(|e| {
+            eprintln!("Write error: {}", e);
+        })
+}
+
+fn run_blit(args: &ArgMatches) -> Result<(), ()> {
+    let region = Region::load(Path::new(args.value_of("region").unwrap())).map_err(|e| {
+        eprintln!("Could not load region {}", e);
+    })?;
+    let pos = parse_numbers(args.value_of("at").unwrap(), 2)?;
+    let pos = V2::make(pos[0], pos[1]);
+    let target_name = args.value_of("target").unwrap();
+    let target = Path::new(target_name);
+    if !target.is_dir() {
+        let encoding = Encoding::get_encoding("437").map_err(|e| {
+            eprintln!("Could not load encoding {:?}", e);
+        })?;
+        let mut grid = load_page(target, args, &encoding)?;
+        if !region.triggers.is_empty() {
+            eprintln!(
+                "Warning: single page can't hold triggers, {} triggers not copied",
+                region.triggers.len()
+            );
+        }
+        region.blit(&mut grid, &mut Vec::new(), pos).map_err(|e| {
+            eprintln!("Invalid region: {}", e);
+        })?;
+        return save_page(&grid, args.value_of("output"), target_name, args, &encoding);
+    }
+
+    let output_dir = Path::new(args.value_of("output").ok_or_else(|| {
+        eprintln!("Blitting into folder level requires output folder");
+    })?);
+    let level = load_level_pages(target)?;
+    let id = parse_page_id(args.value_of("page").unwrap())?;
+    let page = level.get(id).ok_or_else(|| {
+        eprintln!("Page {:02x} not found in level", id);
+    })?;
+    let mut grid = page.grid.clone();
+    let mut triggers = page.triggers.clone();
+    region.blit(&mut grid, &mut triggers, pos).map_err(|e| {
+        eprintln!("Invalid region: {}", e);
+    })?;
+    copy_level(target, output_dir)?;
+    let output_path = output_dir.join(page.path.file_name().unwrap());
+    File::create(&output_path)
+        .and_then(|mut f| grid.save_format(&mut f, page.format, &level.encoding))
+        .map_err(|e| {
+            eprintln!("Write error: {}", e);
+        })?;
+    LevelPages::save_triggers(target, output_dir, page, triggers).map_err(|e| {
+        eprintln!("Could not write level config {}", e);
+    })
+}
+
+fn run_export_image(args: &ArgMatches) -> Result<(), ()> {
+    let page_name = args.value_of("page").unwrap();
+    let output = args.value_of("output").unwrap();
+    let format = ImageFormat::from_path(Path::new(output)).ok_or_else(|| {
+        eprintln!("Unknown image format {}, use .pbm, .pgm or .ppm", output);
+    })?;
+    let encoding = Encoding::get_encoding("437").map_err(|e| {
+        eprintln!("Could not load encoding {:?}", e);
+    })?;
+    let grid = load_page(Path::new(page_name), args, &encoding)?;
+    let plane = match (args.value_of("plane"), format) {
+        (Some(plane), _) => Some(plane.parse::<u8>().unwrap()),
+        (None, ImageFormat::Pbm) => Some(PLAYER_OFFSET as u8),
+        (None, _) => None,
+    };
+    File::create(Path::new(output))
+        .and_then(|mut f| netpbm::write_image(&mut f, &grid, format, plane))
+        .map_err(|e| {
+            eprintln!("Write error: {}", e);
+        })
+}
+
+fn run_import_plane(args: &ArgMatches) -> Result<(), ()> {
+    let page_name = args.value_of("page").unwrap();
+    let image_name = args.value_of("image").unwrap();
+    let plane = args.value_of("plane").unwrap().parse::<u8>().unwrap();
+    let encoding = Encoding::get_encoding("437").map_err(|e| {
+        eprintln!("Could not load encoding {:?}", e);
+    })?;
+    let mut grid = load_page(Path::new(page_name), args, &encoding)?;
+    let bits = std::fs::read(image_name)
+        .and_then(|data| netpbm::read_pbm(&data))
+        .map_err(|e| {
+            eprintln!("Could not read image {}: {}", image_name, e);
+        })?;
+    grid.set_plane_bits(plane, &bits);
+    save_page(&grid, args.value_of("output"), page_name, args, &encoding)
+}
+
 fn run_game(_args: &ArgMatches) -> Result<(), ()> {
     let mut stdout = std::io::stdout();
     {
@@ -113,19 +625,23 @@ fn run_game(_args: &ArgMatches) -> Result<(), ()> {
 
 fn run_single_level(args: &ArgMatches) -> Result<(), ()> {
     let mut stdout = std::io::stdout();
-    {
+    let summary = {
         let game_data = crate::gameplay::GamePlayState::load_from_path(Path::new(
             args.value_of(&"path").unwrap().into(),
         ))
         .map_err(|e| {
             eprintln!("{} ", e);
         })?; //TODO: error handling
+        for warning in &game_data.load_warnings {
+            eprintln!("Warning: {}", warning);
+        }
         let mut context = UiContext::create(&stdout).ok_or(())?;
 
         let mut ui = GamePlayUI::new(&mut context);
         ui.set_state(game_data);
         context.run(&mut ui).map_err(|_| ())?;
-    }
+        ui.take_summary()
+    };
 
     write!(
         stdout,
@@ -134,6 +650,9 @@ fn run_single_level(args: &ArgMatches) -> Result<(), ()> {
         ::termion::cursor::Show
     )
     .map_err(|_| ())?;
+    if let Some(summary) = summary {
+        println!("{}", summary);
+    }
     Ok(())
 }
 
@@ -150,8 +669,15 @@ fn dump_rbsave(args: &ArgMatches) -> Result<(), ()> {
     let mut f = File::open(path).map_err(handle_io_error)?;
     let mut buffer = Vec::new();
     f.read_to_end(&mut buffer).map_err(handle_io_error)?;
-    let v: serde_json::Value =
-        serde_rbbin::from_bytes(&buffer).map_err(|e| eprintln!("Failed to parse file {}", e))?;
+    if args.is_present("schema") {
+        let schema = serde_rbbin::schema(&buffer)
+            .map_err(|e| eprintln!("Failed to parse {}: {}", path_str, e))?;
+        return schema
+            .write(&mut std::io::stdout())
+            .map_err(handle_io_error);
+    }
+    let v: serde_json::Value = serde_rbbin::from_bytes(&buffer)
+        .map_err(|e| eprintln!("Failed to parse {}: {}", path_str, e))?;
     println!(
         "{}",
         serde_json::to_string_pretty(&v).map_err(|e| {
@@ -161,6 +687,87 @@ fn dump_rbsave(args: &ArgMatches) -> Result<(), ()> {
     Ok(())
 }
 
+fn pack_rbsave(args: &ArgMatches) -> Result<(), ()> {
+    let input = args.value_of("input").unwrap();
+    let file = File::open(input).map_err(|e| {
+        eprintln!("Failed to open {}: {}", input, e);
+    })?;
+    let value: serde_json::Value =
+        serde_json::from_reader(std::io::BufReader::new(file)).map_err(|e| {
+            eprintln!("Failed to parse {}: {}", input, e);
+        })?;
+    let data = serde_rbbin::to_bytes(&value).map_err(|e| {
+        eprintln!("Failed to encode {}: {}", input, e);
+    })?;
+    let output = args.value_of("output").unwrap();
+    std::fs::write(output, data).map_err(|e| {
+        eprintln!("Write error: {}", e);
+    })
+}
+
+fn export_rbsave(args: &ArgMatches) -> Result<(), ()> {
+    let path = Path::new(args.value_of("path").unwrap());
+    let game = crate::gameplay::GamePlayState::load_from_path(path).map_err(|e| {
+        eprintln!("{}", e);
+    })?;
+    let output = Path::new(args.value_of("output").unwrap());
+    game.save_rbstorage(output).map_err(|e| {
+        eprintln!("{}", e);
+    })
+}
+
+fn verify_import(args: &ArgMatches) -> Result<(), ()> {
+    use crate::gameplay::GamePlayState;
+    let load = |name, loader: fn(&Path) -> Result<GamePlayState, load_error::LoadError>| {
+        let path = Path::new(args.value_of(name).unwrap());
+        loader(path).map_err(|e| {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+        })
+    };
+    let imported = load("storage", GamePlayState::load_from_rbstorage)?;
+    let reference = load("level", GamePlayState::load_from_folder)?;
+    let report = crate::verify_import::ImportReport::new(&reference, &imported);
+    let stdout = std::io::stdout();
+    report.write_text(&mut stdout.lock()).map_err(|e| {
+        eprintln!("{}", e);
+    })?;
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+fn strict_arg() -> Arg<'static, 'static> {
+    Arg::with_name("strict")
+        .long("strict")
+        .help("Reject overlong lines and files")
+}
+
+fn column_major_arg() -> Arg<'static, 'static> {
+    Arg::with_name("column_major")
+        .long("column-major")
+        .help("Read and write .bin pages column by column")
+}
+
+/// `PLAYER_OFFSET` as clap default value.
+const PLAYER_PLANE: &str = "6";
+
+fn plane_arg() -> Arg<'static, 'static> {
+    Arg::with_name("plane")
+        .long("plane")
+        .takes_value(true)
+        .possible_values(&["0", "1", "2", "3", "4", "5", "6", "7"])
+}
+
+fn page_arg() -> Arg<'static, 'static> {
+    Arg::with_name("page")
+        .long("page")
+        .takes_value(true)
+        .default_value("0x42")
+        .help("Page of folder level")
+}
+
 fn main() {
     let matches = App::new("ethdec")
         .version(crate_version!())
@@ -169,17 +776,168 @@ fn main() {
         .arg(Arg::with_name("encoding").takes_value(true))
         .subcommand(
             clap::SubCommand::with_name("diff")
-                .about("Diff two images")
+                .about("Diff two pages or two versions of folder level")
                 .arg(Arg::with_name("before"))
                 .arg(Arg::with_name("after"))
-                .arg(Arg::with_name("output").short("o").takes_value(true)),
+                .arg(Arg::with_name("output").short("o").takes_value(true))
+                .arg(
+                    Arg::with_name("page")
+                        .long("page")
+                        .takes_value(true)
+                        .default_value("0x42")
+                        .help("Page id recorded in patch when comparing single pages"),
+                )
+                .arg(
+                    Arg::with_name("legacy")
+                        .long("legacy")
+                        .conflicts_with("page")
+                        .help("Write headerless single page patch"),
+                )
+                .arg(
+                    Arg::with_name("reversible")
+                        .long("reversible")
+                        .conflicts_with("legacy")
+                        .help("Record old values so that patch can be checked and reversed"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["binary", "text", "json"])
+                        .default_value("binary")
+                        .help("Write patch or readable list of changes"),
+                )
+                .arg(strict_arg())
+                .arg(column_major_arg()),
         )
         .subcommand(
             clap::SubCommand::with_name("patch")
-                .about("Diff two images")
+                .about("Apply patch to page or folder level")
                 .arg(Arg::with_name("data"))
                 .arg(Arg::with_name("patch"))
-                .arg(Arg::with_name("output").short("o").takes_value(true)),
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Output page, or folder for patched copy of folder level"),
+                )
+                .arg(
+                    Arg::with_name("page")
+                        .long("page")
+                        .takes_value(true)
+                        .help("Page to take from multi-page patch"),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Apply even if page differs from the one patch was made for"),
+                )
+                .arg(
+                    Arg::with_name("reverse")
+                        .long("reverse")
+                        .help("Undo patch made with --reversible"),
+                )
+                .arg(
+                    Arg::with_name("dry_run")
+                        .long("dry-run")
+                        .help("Only report which hunks apply cleanly"),
+                )
+                .arg(strict_arg())
+                .arg(column_major_arg()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("merge")
+                .about("Three-way merge of two edited versions of the same page")
+                .arg(Arg::with_name("base").required(true))
+                .arg(Arg::with_name("ours").required(true))
+                .arg(Arg::with_name("theirs").required(true))
+                .arg(Arg::with_name("output").short("o").takes_value(true))
+                .arg(
+                    Arg::with_name("conflicts")
+                        .long("conflicts")
+                        .takes_value(true)
+                        .help("Write page with conflicting cells marked"),
+                )
+                .arg(strict_arg())
+                .arg(column_major_arg()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("extract")
+                .about("Save rectangular part of page with its triggers as region file")
+                .arg(Arg::with_name("source").required(true))
+                .arg(
+                    Arg::with_name("rect")
+                        .long("rect")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Region as x,y,width,height"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(page_arg())
+                .arg(
+                    plane_arg()
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Bit plane to take, all when not given"),
+                )
+                .arg(strict_arg())
+                .arg(column_major_arg()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("blit")
+                .about("Stamp region file into page or folder level")
+                .arg(Arg::with_name("region").required(true))
+                .arg(Arg::with_name("target").required(true))
+                .arg(
+                    Arg::with_name("at")
+                        .long("at")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Position of top left corner as x,y"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .help("Output page, or folder for modified copy of folder level"),
+                )
+                .arg(page_arg())
+                .arg(strict_arg())
+                .arg(column_major_arg()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("export_image")
+                .about("Save page or a single bit plane of it as PBM/PGM/PPM image")
+                .arg(Arg::with_name("page").required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Output image, format chosen by extension .pbm, .pgm or .ppm"),
+                )
+                .arg(plane_arg().help("Bit plane to export, PBM defaults to the player bit"))
+                .arg(strict_arg())
+                .arg(column_major_arg()),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("import_plane")
+                .about("Replace a bit plane of page with PBM image")
+                .arg(Arg::with_name("page").required(true))
+                .arg(Arg::with_name("image").required(true))
+                .arg(Arg::with_name("output").short("o").takes_value(true))
+                .arg(
+                    plane_arg()
+                        .default_value(PLAYER_PLANE)
+                        .help("Bit plane to replace"),
+                )
+                .arg(strict_arg())
+                .arg(column_major_arg()),
         )
         .subcommand(
             clap::SubCommand::with_name("play")
@@ -189,15 +947,57 @@ fn main() {
         .subcommand(
             clap::SubCommand::with_name("dump_rbsave")
                 .about("Read RB save file and print it as text")
-                .arg(Arg::with_name("path")),
+                .arg(Arg::with_name("path"))
+                .arg(
+                    Arg::with_name("schema")
+                        .long("schema")
+                        .help("Print structure with value types, sizes and byte offsets"),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("pack_rbsave")
+                .about("Encode JSON printed by dump_rbsave back to RB save file")
+                .arg(Arg::with_name("input").required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("export_rbsave")
+                .about("Convert level to RB save file")
+                .arg(Arg::with_name("path").required(true))
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("verify_import")
+                .alias("verify-import")
+                .about("Compare RB save file with folder level")
+                .arg(Arg::with_name("storage").required(true))
+                .arg(Arg::with_name("level").required(true)),
         )
         .get_matches();
 
     let result = match matches.subcommand() {
         ("diff", Some(m)) => run_diff(m),
         ("patch", Some(m)) => run_patch(m),
+        ("merge", Some(m)) => run_merge(m),
+        ("extract", Some(m)) => run_extract(m),
+        ("blit", Some(m)) => run_blit(m),
+        ("export_image", Some(m)) => run_export_image(m),
+        ("import_plane", Some(m)) => run_import_plane(m),
         ("play", Some(m)) => run_single_level(m),
         ("dump_rbsave", Some(m)) => dump_rbsave(m),
+        ("pack_rbsave", Some(m)) => pack_rbsave(m),
+        ("export_rbsave", Some(m)) => export_rbsave(m),
+        ("verify_import", Some(m)) => verify_import(m),
         _ => run_game(&matches),
     };
     ::std::process::exit(match result {
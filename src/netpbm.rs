@@ -0,0 +1,233 @@
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+
+use crate::bytegrid::{ByteGrid, GridOrder};
+
+const SIZE: usize = 256;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImageFormat {
+    /// Black and white, black cells have the bit set.
+    Pbm,
+    /// Grayscale, byte value or 0/255 for single plane.
+    Pgm,
+    /// Color, bits 7-5 red, 4-2 green, 1-0 blue. Single plane drawn in yellow.
+    Ppm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Option<ImageFormat> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("pbm") => Some(ImageFormat::Pbm),
+            Some("pgm") => Some(ImageFormat::Pgm),
+            Some("ppm") => Some(ImageFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_owned())
+}
+
+/// Write page as binary netpbm image. Pbm requires a plane, other formats draw the whole byte
+/// when plane is `None`.
+pub fn write_image(
+    out: &mut Write,
+    grid: &ByteGrid,
+    format: ImageFormat,
+    plane: Option<u8>,
+) -> Result<(), Error> {
+    let bits = plane.map(|plane| grid.plane_bits(plane));
+    match format {
+        ImageFormat::Pbm => {
+            let bits = bits.ok_or_else(|| invalid_data("PBM image requires a bit plane"))?;
+            write!(out, "P4\n{} {}\n", SIZE, SIZE)?;
+            let packed: Vec<u8> = bits
+                .chunks(8)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .fold(0u8, |acc, bit| (acc << 1) | if *bit { 1 } else { 0 })
+                })
+                .collect();
+            out.write_all(&packed)
+        }
+        ImageFormat::Pgm => {
+            write!(out, "P5\n{} {}\n255\n", SIZE, SIZE)?;
+            let pixels: Vec<u8> = match &bits {
+                Some(bits) => bits.iter().map(|bit| if *bit { 255 } else { 0 }).collect(),
+                None => grid.to_bytes(GridOrder::RowMajor),
+            };
+            out.write_all(&pixels)
+        }
+        ImageFormat::Ppm => {
+            write!(out, "P6\n{} {}\n255\n", SIZE, SIZE)?;
+            let mut pixels = Vec::with_capacity(SIZE * SIZE * 3);
+            match &bits {
+                Some(bits) => {
+                    for bit in bits {
+                        let v = if *bit { 255 } else { 0 };
+                        pixels.extend_from_slice(&[v, v, 0]);
+                    }
+                }
+                None => {
+                    for byte in grid.to_bytes(GridOrder::RowMajor) {
+                        let r = (byte >> 5) as u16 * 255 / 7;
+                        let g = ((byte >> 2) & 7) as u16 * 255 / 7;
+                        let b = (byte & 3) as u16 * 255 / 3;
+                        pixels.extend_from_slice(&[r as u8, g as u8, b as u8]);
+                    }
+                }
+            }
+            out.write_all(&pixels)
+        }
+    }
+}
+
+struct Header<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Header<'a> {
+    fn skip_space(&mut self) {
+        while let Some(c) = self.data.get(self.pos) {
+            if *c == b'#' {
+                while self.data.get(self.pos).map_or(false, |c| *c != b'\n') {
+                    self.pos += 1;
+                }
+            } else if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        self.skip_space();
+        let start = self.pos;
+        while self
+            .data
+            .get(self.pos)
+            .map_or(false, |c| c.is_ascii_digit())
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| invalid_data("Bad PBM header"))
+    }
+}
+
+/// Read 256x256 PBM image (plain P1 or binary P4) as row major bits, black is `true`.
+pub fn read_pbm(data: &[u8]) -> Result<Vec<bool>, Error> {
+    let binary = match data.get(..2) {
+        Some(b"P1") => false,
+        Some(b"P4") => true,
+        _ => return Err(invalid_data("Not a PBM image")),
+    };
+    let mut header = Header { data, pos: 2 };
+    let width = header.number()?;
+    let height = header.number()?;
+    if width != SIZE || height != SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Image is {}x{}, expected {}x{}", width, height, SIZE, SIZE),
+        ));
+    }
+    let mut result = Vec::with_capacity(SIZE * SIZE);
+    if binary {
+        // single whitespace separates header from raster
+        let raster = data
+            .get(header.pos + 1..header.pos + 1 + SIZE * SIZE / 8)
+            .ok_or_else(|| invalid_data("PBM image is truncated"))?;
+        for byte in raster {
+            for bit in (0..8).rev() {
+                result.push((byte >> bit) & 1 == 1);
+            }
+        }
+    } else {
+        for c in &data[header.pos..] {
+            match c {
+                b'0' => result.push(false),
+                b'1' => result.push(true),
+                c if c.is_ascii_whitespace() => {}
+                _ => return Err(invalid_data("Unexpected character in PBM image")),
+            }
+            if result.len() == SIZE * SIZE {
+                break;
+            }
+        }
+        if result.len() != SIZE * SIZE {
+            return Err(invalid_data("PBM image is truncated"));
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbm_roundtrip() {
+        let mut grid = ByteGrid::new();
+        grid[(0, 0)] = 0x40;
+        grid[(9, 3)] = 0x41;
+        grid[(255, 255)] = 0xff;
+        let mut out = Vec::new();
+        write_image(&mut out, &grid, ImageFormat::Pbm, Some(6)).unwrap();
+        assert_eq!(out.len(), "P4\n256 256\n".len() + 256 * 256 / 8);
+
+        let bits = read_pbm(&out).unwrap();
+        assert_eq!(bits, grid.plane_bits(6));
+        assert_eq!(bits.iter().filter(|b| **b).count(), 3);
+
+        let mut other = ByteGrid::new();
+        other[(9, 3)] = 0x01;
+        other[(1, 1)] = 0x40;
+        other.set_plane_bits(6, &bits);
+        assert_eq!(other[(0, 0)], 0x40);
+        assert_eq!(other[(9, 3)], 0x41);
+        assert_eq!(other[(1, 1)], 0);
+    }
+
+    #[test]
+    fn plain_pbm() {
+        let mut text = String::from("P1\n# comment\n256 256\n");
+        for y in 0..256 {
+            for x in 0..256 {
+                text.push(if x == y { '1' } else { '0' });
+                text.push(' ');
+            }
+            text.push('\n');
+        }
+        let bits = read_pbm(text.as_bytes()).unwrap();
+        assert!(bits[0]);
+        assert!(!bits[1]);
+        assert!(bits[257]);
+
+        assert!(read_pbm(b"P1\n2 2\n0 1 1 0").is_err());
+        assert!(read_pbm(b"P4\n256 256\n\x00").is_err());
+        assert!(read_pbm(b"P5\n256 256\n").is_err());
+    }
+
+    #[test]
+    fn gray_and_color() {
+        let mut grid = ByteGrid::new();
+        grid[(1, 0)] = 0xff;
+        let mut out = Vec::new();
+        write_image(&mut out, &grid, ImageFormat::Pgm, None).unwrap();
+        let header = "P5\n256 256\n255\n".len();
+        assert_eq!(&out[header..header + 2], &[0, 0xff]);
+
+        let mut out = Vec::new();
+        write_image(&mut out, &grid, ImageFormat::Ppm, None).unwrap();
+        assert_eq!(&out[header + 3..header + 6], &[255, 255, 255]);
+
+        assert!(write_image(&mut Vec::new(), &grid, ImageFormat::Pbm, None).is_err());
+    }
+}
@@ -0,0 +1,353 @@
+use core::fmt;
+
+use crate::bytegrid::{ByteGrid, ByteGridDiff};
+
+const MAGIC: &[u8] = b"BFPATCH\0";
+const VERSION: u8 = 2;
+/// Version with bodies made of legacy sequence records.
+const VERSION_SEQ: u8 = 1;
+const SECTION_HEADER_SIZE: usize = 10;
+/// Section body starts with size of records and is followed by old values.
+const FLAG_OLD_VALUES: u8 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PatchError {
+    UnsupportedVersion(u8),
+    /// Data ends before the structure starting at offset is complete.
+    Truncated(usize),
+    InvalidFlags(usize),
+    InvalidRecord(usize),
+    DuplicatePage(u8),
+    TrailingData(usize),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::UnsupportedVersion(v) => write!(f, "unsupported patch version {}", v),
+            PatchError::Truncated(pos) => write!(f, "patch truncated at offset {}", pos),
+            PatchError::InvalidFlags(pos) => write!(f, "unknown section flags at offset {}", pos),
+            PatchError::InvalidRecord(pos) => write!(f, "invalid hunk record at offset {}", pos),
+            PatchError::DuplicatePage(page) => write!(f, "page {:02x} patched twice", page),
+            PatchError::TrailingData(pos) => write!(f, "trailing data at offset {}", pos),
+        }
+    }
+}
+
+pub struct PagePatch {
+    /// Target page, `None` for headerless patches.
+    pub page: Option<u8>,
+    /// Checksum of the grid diff was made against, `None` for headerless patches.
+    pub base_checksum: Option<u32>,
+    pub diff: ByteGridDiff,
+}
+
+impl PagePatch {
+    pub fn matches_base(&self, base: &ByteGrid) -> bool {
+        self.base_checksum
+            .map_or(true, |checksum| checksum == base.checksum())
+    }
+}
+
+/// Patch file for one or more pages.
+///
+/// Layout: magic `BFPATCH\0`, version byte, page count u16, then for each page: page id u8,
+/// flags u8, base checksum u32, body size u32 and body made of compact `ByteGridDiff` records.
+/// With flag bit 0 set body is records size u32, records and old value of every written cell.
+/// All numbers are little endian. Version 1 files with legacy records in body and files without
+/// magic, read as single headerless diff, are also accepted.
+pub struct Patch {
+    pub pages: Vec<PagePatch>,
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Result<u16, PatchError> {
+    data.get(pos..pos + 2)
+        .map(|v| v[0] as u16 | (v[1] as u16) << 8)
+        .ok_or(PatchError::Truncated(pos))
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Result<u32, PatchError> {
+    data.get(pos..pos + 4)
+        .map(|v| v[0] as u32 | (v[1] as u32) << 8 | (v[2] as u32) << 16 | (v[3] as u32) << 24)
+        .ok_or(PatchError::Truncated(pos))
+}
+
+fn push_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8]);
+}
+
+impl Patch {
+    pub fn new() -> Patch {
+        Patch { pages: Vec::new() }
+    }
+
+    /// Add diff between two versions of page. Unchanged pages are skipped.
+    pub fn add_page(&mut self, page: u8, before: &ByteGrid, after: &ByteGrid) {
+        self.push_page(page, before, before.diff(after));
+    }
+
+    /// Same as `add_page` but records old values so that patch can be reversed.
+    pub fn add_page_reversible(&mut self, page: u8, before: &ByteGrid, after: &ByteGrid) {
+        self.push_page(page, before, before.diff_reversible(after));
+    }
+
+    fn push_page(&mut self, page: u8, before: &ByteGrid, diff: ByteGridDiff) {
+        if diff.is_empty() {
+            return;
+        }
+        self.pages.push(PagePatch {
+            page: Some(page),
+            base_checksum: Some(before.checksum()),
+            diff,
+        });
+    }
+
+    pub fn get_page(&self, page: u8) -> Option<&PagePatch> {
+        self.pages.iter().find(|p| p.page == Some(page))
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        result.extend_from_slice(MAGIC);
+        result.push(VERSION);
+        let count = self.pages.len() as u16;
+        result.extend_from_slice(&[count as u8, (count >> 8) as u8]);
+        for page in &self.pages {
+            let mut body = page.diff.serialize_compact();
+            let mut flags = 0;
+            if let Some(old) = page.diff.old_values() {
+                flags |= FLAG_OLD_VALUES;
+                let mut records = body;
+                body = Vec::new();
+                push_u32(&mut body, records.len() as u32);
+                body.append(&mut records);
+                body.extend_from_slice(old);
+            }
+            result.push(page.page.unwrap_or(0));
+            result.push(flags);
+            push_u32(&mut result, page.base_checksum.unwrap_or(0));
+            push_u32(&mut result, body.len() as u32);
+            result.extend_from_slice(&body);
+        }
+        result
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<Patch, PatchError> {
+        if !data.starts_with(MAGIC) {
+            let diff = ByteGridDiff::deserialize(data).map_err(PatchError::InvalidRecord)?;
+            return Ok(Patch {
+                pages: vec![PagePatch {
+                    page: None,
+                    base_checksum: None,
+                    diff,
+                }],
+            });
+        }
+        let mut pos = MAGIC.len();
+        let version = *data.get(pos).ok_or(PatchError::Truncated(pos))?;
+        if version != VERSION && version != VERSION_SEQ {
+            return Err(PatchError::UnsupportedVersion(version));
+        }
+        pos += 1;
+        let count = read_u16(data, pos)?;
+        pos += 2;
+        let mut result = Patch::new();
+        for _ in 0..count {
+            let header = data
+                .get(pos..pos + SECTION_HEADER_SIZE)
+                .ok_or(PatchError::Truncated(pos))?;
+            let page = header[0];
+            let flags = header[1];
+            let known_flags = if version == VERSION {
+                FLAG_OLD_VALUES
+            } else {
+                0
+            };
+            if flags & !known_flags != 0 {
+                return Err(PatchError::InvalidFlags(pos + 1));
+            }
+            let base_checksum = read_u32(data, pos + 2)?;
+            let size = read_u32(data, pos + 6)? as usize;
+            pos += SECTION_HEADER_SIZE;
+            let mut body = data
+                .get(pos..pos.saturating_add(size))
+                .ok_or(PatchError::Truncated(pos))?;
+            let mut body_pos = pos;
+            let mut old = None;
+            if flags & FLAG_OLD_VALUES != 0 {
+                let records_size = read_u32(body, 0).map_err(|_| PatchError::Truncated(pos))?;
+                let records_end = (records_size as usize).saturating_add(4);
+                if records_end > body.len() {
+                    return Err(PatchError::Truncated(pos));
+                }
+                old = Some(body[records_end..].to_vec());
+                body = &body[4..records_end];
+                body_pos += 4;
+            }
+            let mut diff = if version == VERSION_SEQ {
+                ByteGridDiff::deserialize(body)
+            } else {
+                ByteGridDiff::deserialize_compact(body)
+            }
+            .map_err(|offset| PatchError::InvalidRecord(body_pos + offset))?;
+            if let Some(old) = old {
+                if !diff.set_old_values(old) {
+                    return Err(PatchError::InvalidRecord(body_pos + body.len()));
+                }
+            }
+            if result.get_page(page).is_some() {
+                return Err(PatchError::DuplicatePage(page));
+            }
+            result.pages.push(PagePatch {
+                page: Some(page),
+                base_checksum: Some(base_checksum),
+                diff,
+            });
+            pos += size;
+        }
+        if pos != data.len() {
+            return Err(PatchError::TrailingData(pos));
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pages() -> (ByteGrid, ByteGrid, ByteGrid) {
+        let a = ByteGrid::new();
+        let mut b = ByteGrid::new();
+        b[(1, 2)] = 5;
+        b[(200, 100)] = 6;
+        let mut c = b.clone();
+        c[(3, 3)] = 7;
+        (a, b, c)
+    }
+
+    #[test]
+    fn multi_page_roundtrip() {
+        let (a, b, c) = test_pages();
+        let mut patch = Patch::new();
+        patch.add_page(0x42, &a, &b);
+        patch.add_page(0x02, &b, &c);
+        patch.add_page(0x03, &c, &c);
+        assert_eq!(patch.pages.len(), 2);
+
+        let data = patch.serialize();
+        let parsed = Patch::deserialize(&data).unwrap();
+        assert_eq!(parsed.pages.len(), 2);
+
+        let page = parsed.get_page(0x42).unwrap();
+        assert!(page.matches_base(&a));
+        assert!(!page.matches_base(&b));
+        let mut patched = a.clone();
+        patched.patch(&page.diff);
+        assert!(patched == b);
+
+        let page = parsed.get_page(0x02).unwrap();
+        assert!(page.matches_base(&b));
+        let mut patched = b.clone();
+        patched.patch(&page.diff);
+        assert!(patched == c);
+        assert!(parsed.get_page(0x03).is_none());
+    }
+
+    #[test]
+    fn legacy_patch() {
+        let (a, b, _) = test_pages();
+        let data = a.diff(&b).serialize();
+        let parsed = Patch::deserialize(&data).unwrap();
+        assert_eq!(parsed.pages.len(), 1);
+        assert_eq!(parsed.pages[0].page, None);
+        assert!(parsed.pages[0].matches_base(&b));
+        let mut patched = a.clone();
+        patched.patch(&parsed.pages[0].diff);
+        assert!(patched == b);
+    }
+
+    #[test]
+    fn old_values() {
+        let (a, b, c) = test_pages();
+        let mut patch = Patch::new();
+        patch.add_page_reversible(0x42, &a, &b);
+        patch.add_page(0x43, &b, &c);
+        let data = patch.serialize();
+        let parsed = Patch::deserialize(&data).unwrap();
+        let diff = &parsed.get_page(0x42).unwrap().diff;
+        assert_eq!(diff.old_values(), patch.pages[0].diff.old_values());
+        let mut patched = b.clone();
+        patched.patch(&diff.reverse().unwrap());
+        assert!(patched == a);
+        assert!(parsed.get_page(0x43).unwrap().diff.old_values().is_none());
+
+        // old values not matching the records
+        let mut short = Patch::new();
+        short.add_page_reversible(0x42, &a, &b);
+        let mut data = short.serialize();
+        data.pop();
+        let size_pos = MAGIC.len() + 3 + 6;
+        data[size_pos] -= 1;
+        assert!(match Patch::deserialize(&data) {
+            Err(PatchError::InvalidRecord(_)) => true,
+            _ => false,
+        });
+    }
+
+    #[test]
+    fn version_1() {
+        let (a, b, _) = test_pages();
+        let body = a.diff(&b).serialize();
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[VERSION_SEQ, 1, 0, 0x42, 0]);
+        push_u32(&mut data, a.checksum());
+        push_u32(&mut data, body.len() as u32);
+        data.extend_from_slice(&body);
+        let parsed = Patch::deserialize(&data).unwrap();
+        let page = parsed.get_page(0x42).unwrap();
+        assert!(page.matches_base(&a));
+        let mut patched = a.clone();
+        patched.patch(&page.diff);
+        assert!(patched == b);
+    }
+
+    #[test]
+    fn strict_parsing() {
+        let (a, b, c) = test_pages();
+        let mut patch = Patch::new();
+        patch.add_page(0x42, &a, &b);
+        let data = patch.serialize();
+
+        for len in MAGIC.len()..data.len() {
+            assert!(Patch::deserialize(&data[..len]).is_err(), "length {}", len);
+        }
+
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert_eq!(
+            Patch::deserialize(&trailing).err(),
+            Some(PatchError::TrailingData(data.len()))
+        );
+
+        let mut version = data.clone();
+        version[MAGIC.len()] = 9;
+        assert_eq!(
+            Patch::deserialize(&version).err(),
+            Some(PatchError::UnsupportedVersion(9))
+        );
+
+        let mut flags = data.clone();
+        flags[MAGIC.len() + 4] = 2;
+        assert_eq!(
+            Patch::deserialize(&flags).err(),
+            Some(PatchError::InvalidFlags(MAGIC.len() + 4))
+        );
+
+        patch.add_page(0x42, &b, &c);
+        assert_eq!(
+            Patch::deserialize(&patch.serialize()).err(),
+            Some(PatchError::DuplicatePage(0x42))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::bytegrid::ByteGrid;
+use crate::gameplay::Trigger;
+use crate::load_error::{LoadError, LoadErrorKind};
+use tgame::vecmath::{Rectangle, V2};
+
+/// Rectangular part of a page, written by `extract` and stamped into pages by `blit`.
+#[derive(Serialize, Deserialize)]
+pub struct Region {
+    pub width: u16,
+    pub height: u16,
+    /// Bit planes carried by the region, other bits of the target are kept.
+    #[serde(default = "Region::all_planes")]
+    pub mask: u8,
+    /// Rows of space separated hex bytes.
+    pub data: Vec<String>,
+    /// Triggers with positions relative to the top left corner.
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+}
+
+impl Region {
+    fn all_planes() -> u8 {
+        0xff
+    }
+
+    /// Cut `rect` out of page. Triggers inside it are moved to region coordinates.
+    pub fn extract(grid: &ByteGrid, rect: &Rectangle, mask: u8, triggers: &[Trigger]) -> Region {
+        let rows = grid.extract(rect);
+        let width = rows.first().map_or(0, |row| row.len());
+        let data = rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|v| format!("{:02x}", v & mask))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let area = Rectangle {
+            pos: V2::make(rect.pos.x.max(0), rect.pos.y.max(0)),
+            size: V2::make(width as i32, rows.len() as i32),
+        };
+        Region {
+            width: width as u16,
+            height: rows.len() as u16,
+            mask,
+            data,
+            triggers: triggers
+                .iter()
+                .filter(|trigger| contains(&area, trigger.pos()))
+                .map(|trigger| trigger.moved(&area, V2::new() - area.pos))
+                .collect(),
+        }
+    }
+
+    fn area(&self) -> Rectangle {
+        Rectangle {
+            pos: V2::new(),
+            size: V2::make(self.width as i32, self.height as i32),
+        }
+    }
+
+    /// Cells of the region, checked against its size.
+    pub fn rows(&self) -> Result<Vec<Vec<u8>>, String> {
+        if self.data.len() != self.height as usize {
+            return Err(format!(
+                "region has {} rows, expected {}",
+                self.data.len(),
+                self.height
+            ));
+        }
+        let mut result = Vec::new();
+        for (i, line) in self.data.iter().enumerate() {
+            let row = line
+                .split_whitespace()
+                .map(|v| u8::from_str_radix(v, 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("row {} has invalid hex byte", i + 1))?;
+            if row.len() != self.width as usize {
+                return Err(format!(
+                    "row {} has {} bytes, expected {}",
+                    i + 1,
+                    row.len(),
+                    self.width
+                ));
+            }
+            result.push(row);
+        }
+        Ok(result)
+    }
+
+    /// Stamp region into page at `pos`. Triggers of the page inside the covered area are
+    /// replaced with the ones carried by the region.
+    pub fn blit(
+        &self,
+        grid: &mut ByteGrid,
+        triggers: &mut Vec<Trigger>,
+        pos: V2,
+    ) -> Result<(), String> {
+        let rows = self.rows()?;
+        grid.blit(&rows, pos, self.mask);
+        let target = Rectangle {
+            pos,
+            size: self.area().size,
+        };
+        triggers.retain(|trigger| !contains(&target, trigger.pos()));
+        let page = Rectangle {
+            pos: V2::new(),
+            size: V2::make(256, 256),
+        };
+        triggers.extend(
+            self.triggers
+                .iter()
+                .map(|trigger| trigger.moved(&self.area(), pos))
+                .filter(|trigger| contains(&page, trigger.pos())),
+        );
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Region, LoadError> {
+        let file = std::fs::File::open(path).map_err(|e| LoadError::io(path, e))?;
+        let region: Region = serde_yaml::from_reader(file)
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Region(e.to_string())))?;
+        region
+            .rows()
+            .map_err(|e| LoadError::new(path, LoadErrorKind::Region(e)))?;
+        Ok(region)
+    }
+
+    pub fn save(&self, out: &mut Write) -> std::io::Result<()> {
+        serde_yaml::to_writer(out, self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))
+    }
+}
+
+pub(crate) fn contains(rect: &Rectangle, p: V2) -> bool {
+    p.x >= rect.left() && p.x <= rect.right() && p.y >= rect.top() && p.y <= rect.bottom()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trigger(yaml: &str) -> Trigger {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn extract_and_blit() {
+        let mut source = ByteGrid::from_raw_str(b"......\n.#A#..\n.#B#..\n......");
+        source[(2, 2)] |= 0x80;
+        let triggers = vec![
+            trigger("pos: {x: 2, y: 1}\neffect: {SetPC: 514}"),
+            trigger("pos: {x: 5, y: 3}\neffect: EndOfLevel"),
+        ];
+        let rect = Rectangle {
+            pos: V2::make(1, 1),
+            size: V2::make(3, 2),
+        };
+        let region = Region::extract(&source, &rect, 0x7f, &triggers);
+        assert_eq!(region.data, vec!["23 41 23", "23 42 23"]);
+        assert_eq!(region.triggers.len(), 1);
+        assert_eq!(region.triggers[0].pos(), V2::make(1, 0));
+
+        let mut out = Vec::new();
+        region.save(&mut out).unwrap();
+        let region: Region = serde_yaml::from_slice(&out).unwrap();
+
+        let mut target = ByteGrid::new();
+        target[(11, 20)] = 0x80;
+        let mut target_triggers = vec![
+            trigger("pos: {x: 12, y: 21}\neffect: EndOfLevel"),
+            trigger("pos: {x: 0, y: 0}\neffect: EndOfLevel"),
+        ];
+        region
+            .blit(&mut target, &mut target_triggers, V2::make(10, 20))
+            .unwrap();
+        assert_eq!(target[(10, 20)], b'#');
+        assert_eq!(target[(11, 20)], b'A' | 0x80);
+        assert_eq!(target[(11, 21)], b'B');
+        assert_eq!(target_triggers.len(), 2);
+        assert_eq!(target_triggers[1].pos(), V2::make(11, 20));
+        let expected = trigger("pos: {x: 11, y: 20}\neffect: {SetPC: 2837}");
+        assert_eq!(
+            serde_yaml::to_string(&target_triggers[1]).unwrap(),
+            serde_yaml::to_string(&expected).unwrap()
+        );
+
+        let mut broken = region;
+        broken.data.pop();
+        assert!(broken.rows().is_err());
+    }
+}
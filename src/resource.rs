@@ -19,6 +19,17 @@ pub fn get_resource_dir() -> Result<PathBuf, std::io::Error> {
     Err(Error::new(ErrorKind::NotFound, "Resource dir not found"))
 }
 
+/// Per user data: `$XDG_DATA_HOME/bitflip`, falling back to `~/.local/share/bitflip`.
+pub fn get_user_dir() -> Result<PathBuf, std::io::Error> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir).join("bitflip"));
+    }
+    if let Some(home) = std::env::var_os("HOME").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(home).join(".local/share/bitflip"));
+    }
+    Err(Error::new(ErrorKind::NotFound, "User data dir not found"))
+}
+
 #[cfg(test)]
 pub fn get_test_data_dir() -> Result<PathBuf, std::io::Error> {
     let current_exe = ::std::env::current_exe()?;
@@ -37,3 +48,45 @@ pub fn get_test_data_dir() -> Result<PathBuf, std::io::Error> {
     }
     Err(Error::new(ErrorKind::NotFound, "Testdata dir not found"))
 }
+
+/// File or folder path in temp dir unique for this test, removed when dropped.
+#[cfg(test)]
+pub struct TempPath(PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub fn new(name: &str) -> TempPath {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+        let name = format!("bitflip_{}_{}_{}", std::process::id(), n, name);
+        TempPath(std::env::temp_dir().join(name))
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempPath {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempPath {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = if self.0.is_dir() {
+            std::fs::remove_dir_all(&self.0)
+        } else {
+            std::fs::remove_file(&self.0)
+        };
+    }
+}
@@ -0,0 +1,272 @@
+use crate::bytegrid::{ByteGrid, Grid};
+use crate::encoding::Encoding;
+use tgame::vecmath::V2;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum SearchQuery {
+    /// Sequence of bytes in a row.
+    Bytes(Vec<u8>),
+    /// Single cell with bits selected by `mask` equal to `value`.
+    Bits { value: u8, mask: u8 },
+}
+
+/// Matches don't span rows, longer queries can't match anything.
+const MAX_QUERY_LEN: usize = 256;
+
+impl SearchQuery {
+    /// Parse search prompt: `d:65 66` decimal bytes, `x:41 42` hex bytes, `b:01??0000` bit
+    /// pattern with `?` for any bit, anything else is text in `encoding`.
+    pub fn parse(text: &str, encoding: &Encoding) -> Result<SearchQuery, String> {
+        let query = SearchQuery::parse_unchecked(text, encoding)?;
+        if query.len() > MAX_QUERY_LEN {
+            return Err(format!("query longer than {} bytes", MAX_QUERY_LEN));
+        }
+        Ok(query)
+    }
+
+    fn parse_unchecked(text: &str, encoding: &Encoding) -> Result<SearchQuery, String> {
+        let bytes = |values: &str, radix: u32| -> Result<SearchQuery, String> {
+            let result = values
+                .split_whitespace()
+                .map(|v| u8::from_str_radix(v, radix).map_err(|_| format!("bad byte '{}'", v)))
+                .collect::<Result<Vec<u8>, String>>()?;
+            if result.is_empty() {
+                return Err("no bytes to search".to_owned());
+            }
+            Ok(SearchQuery::Bytes(result))
+        };
+        if text.starts_with("d:") {
+            bytes(&text[2..], 10)
+        } else if text.starts_with("x:") {
+            bytes(&text[2..], 16)
+        } else if text.starts_with("b:") {
+            let pattern = &text[2..];
+            if pattern.chars().count() != 8 {
+                return Err("bit pattern needs 8 characters".to_owned());
+            }
+            let (mut value, mut mask) = (0u8, 0u8);
+            for c in pattern.chars() {
+                value <<= 1;
+                mask <<= 1;
+                match c {
+                    '0' => mask |= 1,
+                    '1' => {
+                        mask |= 1;
+                        value |= 1;
+                    }
+                    '?' => {}
+                    _ => return Err(format!("bad bit '{}'", c)),
+                }
+            }
+            Ok(SearchQuery::Bits { value, mask })
+        } else if text.is_empty() {
+            Err("nothing to search".to_owned())
+        } else {
+            let mut result = vec![0u8; text.chars().count()];
+            encoding
+                .decode_utf8(text.chars(), &mut result)
+                .map_err(|e| format!("'{}' is not in the encoding", e.c))?;
+            Ok(SearchQuery::Bytes(result))
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            SearchQuery::Bytes(bytes) => bytes.len(),
+            SearchQuery::Bits { .. } => 1,
+        }
+    }
+
+    /// Start of each match on page in reading order. Matches don't continue to the next row.
+    pub fn find(&self, grid: &ByteGrid) -> Vec<V2> {
+        let mut result = Vec::new();
+        let len = self.len();
+        for y in 0..=255u8 {
+            let row = grid.row(y);
+            let last = match row.len().checked_sub(len) {
+                Some(last) => last,
+                None => return result,
+            };
+            for x in 0..=last {
+                let found = match self {
+                    SearchQuery::Bytes(bytes) => row[x..x + len] == bytes[..],
+                    SearchQuery::Bits { value, mask } => row[x] & mask == *value,
+                };
+                if found {
+                    result.push(V2::make(x as i32, y as i32));
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SearchMark {
+    None,
+    Match,
+    Current,
+}
+
+impl Default for SearchMark {
+    fn default() -> SearchMark {
+        SearchMark::None
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SearchMatch {
+    pub page: u8,
+    pub pos: V2,
+    pub len: usize,
+}
+
+pub struct SearchResults {
+    pub matches: Vec<SearchMatch>,
+    current: usize,
+}
+
+impl SearchResults {
+    /// Search given pages, matches are ordered by page and position.
+    pub fn new<'a, I>(query: &SearchQuery, pages: I) -> SearchResults
+    where
+        I: Iterator<Item = (u8, &'a ByteGrid)>,
+    {
+        let mut pages: Vec<_> = pages.collect();
+        pages.sort_by_key(|(id, _)| *id);
+        let matches = pages
+            .into_iter()
+            .flat_map(|(page, grid)| {
+                query.find(grid).into_iter().map(move |pos| SearchMatch {
+                    page,
+                    pos,
+                    len: query.len(),
+                })
+            })
+            .collect();
+        SearchResults {
+            matches,
+            current: 0,
+        }
+    }
+
+    pub fn current(&self) -> Option<&SearchMatch> {
+        self.matches.get(self.current)
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn next(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + 1) % self.matches.len();
+        }
+    }
+
+    pub fn prev(&mut self) {
+        if !self.matches.is_empty() {
+            self.current = (self.current + self.matches.len() - 1) % self.matches.len();
+        }
+    }
+
+    /// Cells covered by matches on page.
+    pub fn marks(&self, page: u8) -> Grid<SearchMark> {
+        let mut result = Grid::new();
+        for (i, m) in self.matches.iter().enumerate() {
+            if m.page != page {
+                continue;
+            }
+            let mark = if i == self.current {
+                SearchMark::Current
+            } else {
+                SearchMark::Match
+            };
+            for dx in 0..m.len as i32 {
+                let cell = &mut result[m.pos + V2::make(dx, 0)];
+                if *cell != SearchMark::Current {
+                    *cell = mark;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let encoding = Encoding::get_encoding("437").unwrap();
+        let parse = |text| SearchQuery::parse(text, &encoding);
+        assert_eq!(parse("d:65 0"), Ok(SearchQuery::Bytes(vec![65, 0])));
+        assert_eq!(parse("x:41 ff"), Ok(SearchQuery::Bytes(vec![0x41, 0xff])));
+        assert_eq!(parse("AB"), Ok(SearchQuery::Bytes(vec![0x41, 0x42])));
+        assert_eq!(parse("█"), Ok(SearchQuery::Bytes(vec![0xdb])));
+        assert_eq!(
+            parse("b:01????00"),
+            Ok(SearchQuery::Bits {
+                value: 0x40,
+                mask: 0xc3
+            })
+        );
+        assert!(parse("x:4g").is_err());
+        assert!(parse("d:").is_err());
+        assert!(parse("b:0101").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn long_query() {
+        let encoding = Encoding::get_encoding("437").unwrap();
+        let text = "a".repeat(257);
+        assert!(SearchQuery::parse(&text, &encoding).is_err());
+        let hex = format!("x:{}", vec!["41"; 257].join(" "));
+        assert!(SearchQuery::parse(&hex, &encoding).is_err());
+        assert!(SearchQuery::parse(&text[1..], &encoding).is_ok());
+
+        let grid = ByteGrid::from_raw_str(b"aaaa");
+        assert!(SearchQuery::Bytes(vec![b'a'; 257]).find(&grid).is_empty());
+    }
+
+    #[test]
+    fn find_and_cycle() {
+        let a = ByteGrid::from_raw_str(b"abab\nxxab");
+        let b = ByteGrid::from_raw_str(b"..ab");
+        let query = SearchQuery::Bytes(b"ab".to_vec());
+        assert_eq!(
+            query.find(&a),
+            vec![V2::make(0, 0), V2::make(2, 0), V2::make(2, 1)]
+        );
+
+        let mut results = SearchResults::new(&query, vec![(9, &b), (3, &a)].into_iter());
+        assert_eq!(results.matches.len(), 4);
+        assert_eq!(results.current().unwrap().page, 3);
+        results.prev();
+        assert_eq!(
+            results.current(),
+            Some(&SearchMatch {
+                page: 9,
+                pos: V2::make(2, 0),
+                len: 2
+            })
+        );
+        results.next();
+        results.next();
+        assert_eq!(results.current().unwrap().pos, V2::make(2, 0));
+
+        let marks = results.marks(3);
+        assert_eq!(marks[(0, 0)], SearchMark::Match);
+        assert_eq!(marks[(2, 0)], SearchMark::Current);
+        assert_eq!(marks[(3, 0)], SearchMark::Current);
+        assert_eq!(marks[(4, 0)], SearchMark::None);
+
+        let bits = SearchQuery::Bits {
+            value: 0x40,
+            mask: 0x40,
+        };
+        assert_eq!(bits.find(&b).len(), 2);
+    }
+}
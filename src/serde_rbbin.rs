@@ -1,65 +1,203 @@
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use core::fmt;
-use serde::de::{self, Deserialize, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::de::{
+    self, Deserialize, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::ser;
 
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::Display;
 use std::io::{Cursor, Seek, SeekFrom};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Clone, Debug, PartialEq)]
-pub enum Error {
+pub enum ErrorKind {
     Message(String),
     Eof,
     Syntax,
     TrailingCharacters,
+    /// Input exceeds one of `Limits`.
+    LimitExceeded(&'static str),
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::Message(msg) => formatter.write_str(msg),
+            ErrorKind::Eof => formatter.write_str("unexpected end of input"),
+            ErrorKind::Syntax => formatter.write_str("Syntax error"),
+            ErrorKind::TrailingCharacters => formatter.write_str("Trailing characters"),
+            ErrorKind::LimitExceeded(limit) => write!(formatter, "Too many {}", limit),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// Byte offset in input. Errors raised by visitors are placed where reading stopped.
+    pub offset: Option<u64>,
+    /// Keys and list indices leading to the failed value, for example `jumps[3].realm`.
+    pub path: String,
 }
 
 impl Error {
-    fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+    fn new(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            offset: None,
+            path: String::new(),
+        }
+    }
+
+    fn at(offset: u64, kind: ErrorKind) -> Self {
+        Error {
+            offset: Some(offset),
+            ..Error::new(kind)
+        }
+    }
+
+    fn message<T: Display>(offset: u64, msg: T) -> Self {
+        Error::at(offset, ErrorKind::Message(msg.to_string()))
     }
 }
 
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
-        Error::Message(msg.to_string())
+        Error::new(ErrorKind::Message(msg.to_string()))
     }
 }
 
 impl Display for Error {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str(std::error::Error::description(self))
-    }
-}
-
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::Message(ref msg) => msg,
-            Error::Eof => "unexpected end of input",
-            Error::Syntax => "Syntax error",
-            Error::TrailingCharacters => "Trailing characters",
+        write!(formatter, "{}", self.kind)?;
+        if let Some(offset) = self.offset {
+            write!(formatter, " at offset {}", offset)?;
         }
+        if !self.path.is_empty() {
+            write!(formatter, " in {}", self.path)?;
+        }
+        Ok(())
     }
 }
 
+impl std::error::Error for Error {}
+
 enum DeserializerState {
     Typed,
     ValueToken,
 }
 
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// Bounds for reading untrusted input. Strings and lists are also checked against remaining
+/// input before anything is allocated for them.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Maps and lists inside each other.
+    pub max_depth: usize,
+    pub max_list_len: usize,
+    /// Map entries and list elements in whole input. Default leaves room for all 256 realms
+    /// being full, 65536 cells each, and as many other values.
+    pub max_values: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_depth: 64,
+            max_list_len: 1 << 16,
+            max_values: 257 << 16,
+        }
+    }
+}
+
 pub struct Deserializer<'de> {
     input: Cursor<&'de [u8]>,
     state: DeserializerState,
+    /// Location of value being read, left as is when reading fails.
+    path: Vec<PathSegment>,
+    limits: Limits,
+    depth: usize,
+    values: usize,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn from_bytes(input: &'de [u8]) -> Self {
+        Deserializer::with_limits(input, Limits::default())
+    }
+
+    pub fn with_limits(input: &'de [u8], limits: Limits) -> Self {
         Deserializer {
             input: Cursor::new(input),
             state: DeserializerState::Typed,
+            path: Vec::new(),
+            limits,
+            depth: 0,
+            values: 0,
+        }
+    }
+
+    fn remaining(&self) -> u64 {
+        (self.input.get_ref().len() as u64).saturating_sub(self.input.position())
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth {
+            return Err(Error::at(
+                self.input.position(),
+                ErrorKind::LimitExceeded("nested values"),
+            ));
         }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn count_value(&mut self) -> Result<()> {
+        self.values += 1;
+        if self.values > self.limits.max_values {
+            return Err(Error::at(
+                self.input.position(),
+                ErrorKind::LimitExceeded("values"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn path_string(&self) -> String {
+        let mut result = String::new();
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(key) => {
+                    if !result.is_empty() {
+                        result.push('.');
+                    }
+                    result.push_str(key);
+                }
+                PathSegment::Index(i) => result.push_str(&format!("[{}]", i)),
+            }
+        }
+        result
+    }
+
+    /// Fill in location of error from current reading state.
+    fn locate(&self, mut error: Error) -> Error {
+        if error.offset.is_none() {
+            error.offset = Some(self.input.position());
+        }
+        if error.path.is_empty() {
+            error.path = self.path_string();
+        }
+        error
     }
 }
 
@@ -67,49 +205,69 @@ pub fn from_bytes<'a, T>(s: &'a [u8]) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_bytes(s);
-    let t = T::deserialize(&mut deserializer)?;
+    from_bytes_with_limits(s, Limits::default())
+}
+
+pub fn from_bytes_with_limits<'a, T>(s: &'a [u8], limits: Limits) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::with_limits(s, limits);
+    let t = T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))?;
+    let pos = deserializer.input.position();
     if !deserializer.input.read_u8().is_ok() {
         Ok(t)
     } else {
-        Err(Error::TrailingCharacters)
+        Err(Error::at(pos, ErrorKind::TrailingCharacters))
     }
 }
 
 impl<'de> Deserializer<'de> {
     fn peek_byte(&mut self) -> Result<u8> {
-        match self.input.get_ref().get(self.input.position() as usize) {
+        let pos = self.input.position();
+        match self.input.get_ref().get(pos as usize) {
             Some(v) => Ok(*v),
-            None => Err(Error::Eof),
+            None => Err(Error::at(pos, ErrorKind::Eof)),
         }
     }
 
     fn next_byte(&mut self) -> Result<u8> {
-        self.input.read_u8().map_err(|_| Error::Eof)
+        let pos = self.input.position();
+        self.input
+            .read_u8()
+            .map_err(|_| Error::at(pos, ErrorKind::Eof))
     }
 
     fn read_u32(&mut self) -> Result<u32> {
+        let pos = self.input.position();
         let t = self.input.read_u32::<LittleEndian>();
-        t.map_err(|_| Error::Eof)
+        t.map_err(|_| Error::at(pos, ErrorKind::Eof))
     }
 
     fn read_i32(&mut self) -> Result<i32> {
+        let pos = self.input.position();
         self.input
             .read_i32::<LittleEndian>()
-            .map_err(|_| Error::Eof)
+            .map_err(|_| Error::at(pos, ErrorKind::Eof))
     }
 
-    fn read_str(&mut self, size: usize) -> Result<&'de str> {
-        let r = &self
-            .input
-            .get_ref()
-            .get(self.input.position() as usize..)
+    fn read_bytes(&mut self, size: usize) -> Result<&'de [u8]> {
+        let input: &'de [u8] = self.input.get_ref();
+        let pos = self.input.position();
+        let r = input
+            .get(pos as usize..)
             .and_then(|v| v.get(..size))
-            .ok_or(Error::Eof)?;
+            .ok_or(Error::at(pos, ErrorKind::Eof))?;
         self.input
             .seek(SeekFrom::Current(size as i64))
-            .map_err(|_| Error::Message("Seek error should not happen".to_owned()))?;
-        std::str::from_utf8(r).map_err(|_| Error::Message("Bad string".to_owned()))
+            .map_err(|_| Error::message(pos, "Seek error should not happen"))?;
+        Ok(r)
+    }
+
+    fn read_str(&mut self, size: usize) -> Result<&'de str> {
+        let pos = self.input.position();
+        let r = self.read_bytes(size)?;
+        std::str::from_utf8(r).map_err(|_| Error::message(pos, "Bad string"))
     }
 
     fn read_str_size<'s>(&'s mut self) -> Result<&'de str>
@@ -120,14 +278,32 @@ impl<'de> Deserializer<'de> {
         self.read_str(size as usize)
     }
 
+    fn read_bytes_size(&mut self) -> Result<&'de [u8]> {
+        let size = self.read_u32()?;
+        self.read_bytes(size as usize)
+    }
+
+    /// End of map, `c` token.
+    fn expect_end(&mut self) -> Result<()> {
+        let pos = self.input.position();
+        match self.next_byte()? {
+            b'c' => Ok(()),
+            v => Err(Error::message(
+                pos,
+                format!("Expected end of map got {}", v),
+            )),
+        }
+    }
+
     fn read_str_int<T>(&mut self) -> Result<T>
     where
         T: std::str::FromStr,
         T: 'static,
     {
+        let pos = self.input.position();
         self.read_str_size()?
             .parse::<T>()
-            .map_err(|_| Error::Syntax)
+            .map_err(|_| Error::at(pos, ErrorKind::Syntax))
     }
 }
 
@@ -144,10 +320,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
                 Ok(1) | Ok(b'c') => self.deserialize_map(visitor),
                 Ok(2) => self.deserialize_map(visitor),
                 Ok(3) => self.deserialize_seq(visitor),
-                Ok(a) => {
-                    let pos = self.input.position();
-                    Err(Error::Message(format!("Unexpected type {} at {}", a, pos)))
-                }
+                Ok(a) => Err(Error::message(
+                    self.input.position(),
+                    format!("Unexpected type {}", a),
+                )),
                 Err(v) => Err(v),
             },
         }
@@ -216,25 +392,30 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         visitor.visit_u64(self.read_str_int()?)
     }
 
-    fn deserialize_f32<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_f32(self.read_str_int()?)
     }
 
-    fn deserialize_f64<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_f64(self.read_str_int()?)
     }
 
-    fn deserialize_char<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        let pos = self.input.position();
+        let mut chars = self.read_str_size()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::message(pos, "Expected single character")),
+        }
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
@@ -251,34 +432,44 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_str(visitor)
     }
 
-    // The `Serializer` implementation on the previous page serialized byte
-    // arrays as JSON arrays of bytes. Handle that representation here.
-    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    // Byte arrays are stored same way as strings but without utf-8 check.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        visitor.visit_borrowed_bytes(self.read_bytes_size()?)
     }
 
-    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value>
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_bytes(visitor)
     }
 
-    fn deserialize_option<V>(self, _visitor: V) -> Result<V::Value>
+    // There is no null value, missing field is the only way to represent None.
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!();
+        visitor.visit_some(self)
     }
 
-    fn deserialize_unit<V>(self, _visitor: V) -> Result<V::Value>
+    // Empty string as value or empty map.
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        match self.state {
+            DeserializerState::ValueToken => {
+                let pos = self.input.position();
+                if !self.read_str_size()?.is_empty() {
+                    return Err(Error::message(pos, "Expected empty value"));
+                }
+            }
+            DeserializerState::Typed => self.expect_end()?,
+        }
+        visitor.visit_unit()
     }
 
     // Unit struct means a named value containing no data.
@@ -300,28 +491,40 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        let pos = self.input.position();
         let v = self.next_byte()?;
+        self.enter()?;
         if v == b'c' {
             // empty list
-            return visitor.visit_seq(ListReader::new(&mut self, 0 as usize));
+            let result = visitor.visit_seq(ListReader::new(&mut self, 0 as usize));
+            self.leave();
+            return result;
         }
         if v != 3 {
-            return Err(Error::Message(format!(
-                "Expected list at {} got {}",
-                self.input.position(),
-                v
-            )));
+            return Err(Error::message(pos, format!("Expected list got {}", v)));
         }
+        let count_pos = self.input.position();
         let elements = self.read_u32()?;
+        // each element takes at least one byte
+        if elements as u64 > self.remaining() {
+            return Err(Error::at(count_pos, ErrorKind::Eof));
+        }
+        if elements as usize > self.limits.max_list_len {
+            return Err(Error::at(
+                count_pos,
+                ErrorKind::LimitExceeded("list elements"),
+            ));
+        }
         let result = visitor.visit_seq(ListReader::new(&mut self, elements as usize));
+        self.leave();
         if result.is_ok() {
+            let pos = self.input.position();
             let v = self.next_byte()?;
             if v != b'c' {
-                return Err(Error::Message(format!(
-                    "Expected end of list at {} got {}",
-                    self.input.position(),
-                    v
-                )));
+                return Err(Error::message(
+                    pos,
+                    format!("Expected end of list got {}", v),
+                ));
             }
             result
         } else {
@@ -352,7 +555,10 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_map(MapReader::new(&mut self))
+        self.enter()?;
+        let result = visitor.visit_map(MapReader::new(&mut self));
+        self.leave();
+        result
     }
 
     fn deserialize_struct<V>(
@@ -367,16 +573,30 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         self.deserialize_map(visitor)
     }
 
+    // Unit variants are stored as string value, others as map with single entry keyed by
+    // variant name.
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
         _variants: &'static [&'static str],
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!();
+        match self.state {
+            DeserializerState::ValueToken => {
+                visitor.visit_enum(self.read_str_size()?.into_deserializer())
+            }
+            DeserializerState::Typed => {
+                self.enter()?;
+                let value = visitor.visit_enum(EnumReader { de: &mut *self })?;
+                self.leave();
+                self.path.pop();
+                self.expect_end()?;
+                Ok(value)
+            }
+        }
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -412,19 +632,22 @@ impl<'de, 'a> MapAccess<'de> for MapReader<'a, 'de> {
     where
         K: DeserializeSeed<'de>,
     {
+        let pos = self.de.input.position();
         let kind = self.de.next_byte()?;
-        match kind {
+        let state = match kind {
             b'c' => return Ok(None),
-            1 => {
-                self.de.state = DeserializerState::ValueToken;
-                seed.deserialize(&mut *self.de).map(Some)
-            }
-            2 => {
-                self.de.state = DeserializerState::Typed;
-                seed.deserialize(&mut *self.de).map(Some)
-            }
-            _ => Err(Error::Syntax),
-        }
+            1 => DeserializerState::ValueToken,
+            2 => DeserializerState::Typed,
+            _ => return Err(Error::at(pos, ErrorKind::Syntax)),
+        };
+        self.de.count_value()?;
+        // read key for error path and go back, key deserializer may want a number
+        let key_pos = self.de.input.position();
+        let key = self.de.read_str_size()?;
+        self.de.path.push(PathSegment::Key(key.to_owned()));
+        self.de.input.set_position(key_pos);
+        self.de.state = state;
+        seed.deserialize(&mut *self.de).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
@@ -432,18 +655,84 @@ impl<'de, 'a> MapAccess<'de> for MapReader<'a, 'de> {
         V: DeserializeSeed<'de>,
     {
         // Deserialize a map value.
-        seed.deserialize(&mut *self.de)
+        let value = seed.deserialize(&mut *self.de)?;
+        self.de.path.pop();
+        Ok(value)
+    }
+}
+
+struct EnumReader<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumReader<'a, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self)>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let pos = self.de.input.position();
+        self.de.state = match self.de.next_byte()? {
+            1 => DeserializerState::ValueToken,
+            2 => DeserializerState::Typed,
+            v => {
+                return Err(Error::message(
+                    pos,
+                    format!("Expected enum variant got {}", v),
+                ))
+            }
+        };
+        let variant = self.de.read_str_size()?;
+        self.de.path.push(PathSegment::Key(variant.to_owned()));
+        let value = seed.deserialize(variant.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for EnumReader<'a, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        de::Deserialize::deserialize(self.de)
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_seq(self.de, visitor)
+    }
+
+    fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        de::Deserializer::deserialize_map(self.de, visitor)
     }
 }
 
 struct ListReader<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
     count: usize,
+    index: usize,
 }
 
 impl<'a, 'de> ListReader<'a, 'de> {
     fn new(de: &'a mut Deserializer<'de>, count: usize) -> Self {
-        ListReader { de, count }
+        ListReader {
+            de,
+            count,
+            index: 0,
+        }
     }
 }
 
@@ -458,12 +747,601 @@ impl<'de, 'a> SeqAccess<'de> for ListReader<'a, 'de> {
             return Ok(None);
         }
         self.count -= 1;
+        self.de.count_value()?;
         self.de.state = DeserializerState::Typed;
-        seed.deserialize(&mut *self.de).map(Some)
+        self.de.path.push(PathSegment::Index(self.index));
+        self.index += 1;
+        let value = seed.deserialize(&mut *self.de)?;
+        self.de.path.pop();
+        Ok(Some(value))
+    }
+}
+
+/// Value encoded by `ValueSerializer`, the entry kind is decided by what the value turns out
+/// to be.
+enum Encoded {
+    /// `None`, map entries holding it are left out.
+    Skip,
+    /// Text token without length.
+    Scalar(Vec<u8>),
+    /// Map or list.
+    Compound(Vec<u8>),
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(ErrorKind::Message(msg.to_string()))
+    }
+}
+
+fn write_token(out: &mut Vec<u8>, data: &[u8]) {
+    out.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+    out.extend_from_slice(data);
+}
+
+fn write_entry(out: &mut Vec<u8>, key: &[u8], value: Encoded) {
+    match value {
+        Encoded::Skip => {}
+        Encoded::Scalar(data) => {
+            out.push(1);
+            write_token(out, key);
+            write_token(out, &data);
+        }
+        Encoded::Compound(data) => {
+            out.push(2);
+            write_token(out, key);
+            out.extend_from_slice(&data);
+        }
+    }
+}
+
+/// Encode value as rbbin. Top level value has to be a map or a list. Scalars are written as
+/// text, `None` fields are skipped.
+pub fn to_bytes<T: ser::Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    match value.serialize(ValueSerializer)? {
+        Encoded::Compound(data) => Ok(data),
+        _ => Err(Error::new(ErrorKind::Message(
+            "Top level value must be map or list".to_owned(),
+        ))),
+    }
+}
+
+struct ValueSerializer;
+
+impl ValueSerializer {
+    fn scalar<T: Display>(v: T) -> Result<Encoded> {
+        Ok(Encoded::Scalar(v.to_string().into_bytes()))
+    }
+}
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Encoded;
+    type Error = Error;
+    type SerializeSeq = ListWriter;
+    type SerializeTuple = ListWriter;
+    type SerializeTupleStruct = ListWriter;
+    type SerializeTupleVariant = VariantWriter<ListWriter>;
+    type SerializeMap = MapWriter;
+    type SerializeStruct = MapWriter;
+    type SerializeStructVariant = VariantWriter<MapWriter>;
+
+    fn serialize_bool(self, v: bool) -> Result<Encoded> {
+        ValueSerializer::scalar(v as u8)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Encoded> {
+        ValueSerializer::scalar(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Encoded> {
+        Ok(Encoded::Scalar(v.as_bytes().to_vec()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Encoded> {
+        Ok(Encoded::Scalar(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<Encoded> {
+        Ok(Encoded::Skip)
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, value: &T) -> Result<Encoded> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Encoded> {
+        Ok(Encoded::Scalar(Vec::new()))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Encoded> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Encoded> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Encoded> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Encoded> {
+        Ok(wrap_variant(variant, value.serialize(ValueSerializer)?))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<ListWriter> {
+        Ok(ListWriter {
+            count: 0,
+            data: Vec::new(),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListWriter> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListWriter> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantWriter<ListWriter>> {
+        Ok(VariantWriter {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapWriter> {
+        Ok(MapWriter {
+            key: None,
+            data: Vec::new(),
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapWriter> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<VariantWriter<MapWriter>> {
+        Ok(VariantWriter {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct ListWriter {
+    count: u32,
+    data: Vec<u8>,
+}
+
+impl ser::SerializeSeq for ListWriter {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match value.serialize(ValueSerializer)? {
+            Encoded::Compound(data) => self.data.extend_from_slice(&data),
+            _ => {
+                return Err(Error::new(ErrorKind::Message(format!(
+                    "List element {} is not a map or list",
+                    self.count
+                ))))
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    // Empty lists keep the header, bare `c` would be read back as an empty map.
+    fn end(self) -> Result<Encoded> {
+        let mut result = vec![3];
+        result.write_u32::<LittleEndian>(self.count).unwrap();
+        result.extend_from_slice(&self.data);
+        result.push(b'c');
+        Ok(Encoded::Compound(result))
+    }
+}
+
+impl ser::SerializeTuple for ListWriter {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for ListWriter {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct MapWriter {
+    key: Option<Vec<u8>>,
+    data: Vec<u8>,
+}
+
+impl ser::SerializeMap for MapWriter {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        match key.serialize(ValueSerializer)? {
+            Encoded::Scalar(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::new(ErrorKind::Message(
+                "Map key must be a string or number".to_owned(),
+            ))),
+        }
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().unwrap_or_default();
+        let value = value.serialize(ValueSerializer).map_err(|mut e| {
+            if e.path.is_empty() {
+                e.path = String::from_utf8_lossy(&key).into_owned();
+            }
+            e
+        })?;
+        write_entry(&mut self.data, &key, value);
+        Ok(())
+    }
+
+    fn end(mut self) -> Result<Encoded> {
+        self.data.push(b'c');
+        Ok(Encoded::Compound(self.data))
+    }
+}
+
+impl ser::SerializeStruct for MapWriter {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeMap::serialize_entry(self, key, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// Non-unit enum variant, written as map with single entry.
+struct VariantWriter<W> {
+    variant: &'static str,
+    inner: W,
+}
+
+fn wrap_variant(variant: &str, value: Encoded) -> Encoded {
+    let mut data = Vec::new();
+    write_entry(&mut data, variant.as_bytes(), value);
+    data.push(b'c');
+    Encoded::Compound(data)
+}
+
+impl ser::SerializeTupleVariant for VariantWriter<ListWriter> {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        ser::SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        Ok(wrap_variant(
+            self.variant,
+            ser::SerializeSeq::end(self.inner)?,
+        ))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantWriter<MapWriter> {
+    type Ok = Encoded;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeMap::serialize_entry(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Encoded> {
+        Ok(wrap_variant(
+            self.variant,
+            ser::SerializeMap::end(self.inner)?,
+        ))
+    }
+}
+
+/// Structure of rbbin value as stored, for inspecting files `RBSave` doesn't understand.
+#[derive(Debug, PartialEq)]
+pub struct SchemaNode {
+    /// Offset of value, for map entries the value after key.
+    pub offset: u64,
+    pub kind: SchemaKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SchemaKind {
+    String(String),
+    Map(Vec<(String, SchemaNode)>),
+    List(Vec<SchemaNode>),
+}
+
+/// Maps with more string values than this are summarized instead of listing every entry.
+const SCHEMA_MAP_SUMMARY: usize = 16;
+const SCHEMA_PREVIEW: usize = 24;
+
+impl SchemaNode {
+    fn same_shape(&self, other: &SchemaNode) -> bool {
+        match (&self.kind, &other.kind) {
+            (SchemaKind::String(_), SchemaKind::String(_)) => true,
+            (SchemaKind::Map(a), SchemaKind::Map(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|((ka, va), (kb, vb))| ka == kb && va.same_shape(vb))
+            }
+            (SchemaKind::List(a), SchemaKind::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.same_shape(b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Print one value per line with offset, consecutive list elements of the same shape are
+    /// folded.
+    pub fn write(&self, out: &mut std::io::Write) -> std::io::Result<()> {
+        self.write_named(out, "", 0)
+    }
+
+    fn write_named(
+        &self,
+        out: &mut std::io::Write,
+        name: &str,
+        indent: usize,
+    ) -> std::io::Result<()> {
+        write!(out, "{:8}  {:2$}", self.offset, "", indent * 2)?;
+        if !name.is_empty() {
+            write!(out, "{}: ", name)?;
+        }
+        match &self.kind {
+            SchemaKind::String(value) => {
+                if value.chars().count() > SCHEMA_PREVIEW {
+                    let preview: String = value.chars().take(SCHEMA_PREVIEW).collect();
+                    writeln!(out, "string {:?}... ({} bytes)", preview, value.len())
+                } else {
+                    writeln!(out, "string {:?}", value)
+                }
+            }
+            SchemaKind::Map(entries) => {
+                writeln!(out, "map, {} entries", entries.len())?;
+                let all_strings = entries.iter().all(|(_, value)| match value.kind {
+                    SchemaKind::String(_) => true,
+                    _ => false,
+                });
+                if all_strings && entries.len() > SCHEMA_MAP_SUMMARY {
+                    let keys: Vec<&str> = entries.iter().take(3).map(|(k, _)| &k[..]).collect();
+                    return writeln!(
+                        out,
+                        "{:8}  {:indent$}string values, keys {}, ...",
+                        "",
+                        "",
+                        keys.join(", "),
+                        indent = indent * 2 + 2,
+                    );
+                }
+                for (key, value) in entries {
+                    value.write_named(out, key, indent + 1)?;
+                }
+                Ok(())
+            }
+            SchemaKind::List(items) => {
+                writeln!(out, "list, {} elements", items.len())?;
+                let mut i = 0;
+                while i < items.len() {
+                    items[i].write_named(out, &format!("[{}]", i), indent + 1)?;
+                    let mut end = i + 1;
+                    while end < items.len() && items[end].same_shape(&items[i]) {
+                        end += 1;
+                    }
+                    if end > i + 1 {
+                        let range = if end - 1 == i + 1 {
+                            format!("[{}]", i + 1)
+                        } else {
+                            format!("[{}..{}]", i + 1, end - 1)
+                        };
+                        writeln!(
+                            out,
+                            "{:8}  {:indent$}{}: same as [{}]",
+                            items[i + 1].offset,
+                            "",
+                            range,
+                            i,
+                            indent = indent * 2 + 2,
+                        )?;
+                    }
+                    i = end;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Walk the input without target type, same way as `deserialize_any`.
+pub fn schema(s: &[u8]) -> Result<SchemaNode> {
+    let mut deserializer = Deserializer::from_bytes(s);
+    let node = deserializer
+        .schema_value()
+        .map_err(|e| deserializer.locate(e))?;
+    let pos = deserializer.input.position();
+    if deserializer.remaining() == 0 {
+        Ok(node)
+    } else {
+        Err(Error::at(pos, ErrorKind::TrailingCharacters))
+    }
+}
+
+impl<'de> Deserializer<'de> {
+    fn schema_value(&mut self) -> Result<SchemaNode> {
+        let offset = self.input.position();
+        let kind = match self.peek_byte()? {
+            1 | 2 | b'c' => self.schema_map()?,
+            3 => self.schema_list()?,
+            a => return Err(Error::message(offset, format!("Unexpected type {}", a))),
+        };
+        Ok(SchemaNode { offset, kind })
+    }
+
+    fn schema_map(&mut self) -> Result<SchemaKind> {
+        self.enter()?;
+        let mut entries = Vec::new();
+        loop {
+            let pos = self.input.position();
+            let kind = self.next_byte()?;
+            if kind == b'c' {
+                break;
+            }
+            if kind != 1 && kind != 2 {
+                return Err(Error::at(pos, ErrorKind::Syntax));
+            }
+            self.count_value()?;
+            let key = String::from_utf8_lossy(self.read_bytes_size()?).into_owned();
+            self.path.push(PathSegment::Key(key.clone()));
+            let value = if kind == 1 {
+                let offset = self.input.position();
+                let value = String::from_utf8_lossy(self.read_bytes_size()?).into_owned();
+                SchemaNode {
+                    offset,
+                    kind: SchemaKind::String(value),
+                }
+            } else {
+                self.schema_value()?
+            };
+            self.path.pop();
+            entries.push((key, value));
+        }
+        self.leave();
+        Ok(SchemaKind::Map(entries))
+    }
+
+    fn schema_list(&mut self) -> Result<SchemaKind> {
+        self.next_byte()?;
+        self.enter()?;
+        let count_pos = self.input.position();
+        let count = self.read_u32()?;
+        if count as u64 > self.remaining() {
+            return Err(Error::at(count_pos, ErrorKind::Eof));
+        }
+        if count as usize > self.limits.max_list_len {
+            return Err(Error::at(
+                count_pos,
+                ErrorKind::LimitExceeded("list elements"),
+            ));
+        }
+        let mut items = Vec::new();
+        for i in 0..count as usize {
+            self.count_value()?;
+            self.path.push(PathSegment::Index(i));
+            items.push(self.schema_value()?);
+            self.path.pop();
+        }
+        self.expect_end()?;
+        self.leave();
+        Ok(SchemaKind::List(items))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct RBJump {
     #[serde(default)]
     pub achievement: String,
@@ -473,8 +1351,7 @@ pub struct RBJump {
     pub y: u8,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Default, Debug, PartialEq)]
 pub struct RBSave {
     pub allow_page_instruction: u8,
     pub camera_bottom: i32,
@@ -488,8 +1365,123 @@ pub struct RBSave {
     pub player_y: u8,
     pub program_line: u16,
     pub program_location: u16,
-    pub realm2: std::collections::HashMap<u16, u8>,
-    pub realm42: std::collections::HashMap<u16, u8>,
+    /// Page contents from `realmN` keys, `N` is page id in hex.
+    pub realms: BTreeMap<u8, BTreeMap<u16, u8>>,
+    /// Keys skipped when reading.
+    pub unknown_fields: Vec<String>,
+    /// Fields not present in file, left as zero.
+    pub missing_fields: Vec<&'static str>,
+}
+
+const RBSAVE_FIELDS: &[&str] = &[
+    "allowPageInstruction",
+    "cameraBottom",
+    "compareRegister",
+    "dataRegister",
+    "pageRegister",
+    "jumps",
+    "playerLocation",
+    "playerPage",
+    "playerX",
+    "playerY",
+    "programLine",
+    "programLocation",
+];
+
+fn realm_id(key: &str) -> Option<u8> {
+    if key.len() > 5 && key.starts_with("realm") {
+        u8::from_str_radix(&key[5..], 16).ok()
+    } else {
+        None
+    }
+}
+
+impl<'de> Deserialize<'de> for RBSave {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<RBSave, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(RBSaveVisitor)
+    }
+}
+
+struct RBSaveVisitor;
+
+impl<'de> Visitor<'de> for RBSaveVisitor {
+    type Value = RBSave;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("RB save")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<RBSave, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut result = RBSave::default();
+        let mut found = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "allowPageInstruction" => result.allow_page_instruction = map.next_value()?,
+                "cameraBottom" => result.camera_bottom = map.next_value()?,
+                "compareRegister" => result.compare_register = map.next_value()?,
+                "dataRegister" => result.data_register = map.next_value()?,
+                "pageRegister" => result.page_register = map.next_value()?,
+                "jumps" => result.jumps = map.next_value()?,
+                "playerLocation" => result.player_location = map.next_value()?,
+                "playerPage" => result.player_page = map.next_value()?,
+                "playerX" => result.player_x = map.next_value()?,
+                "playerY" => result.player_y = map.next_value()?,
+                "programLine" => result.program_line = map.next_value()?,
+                "programLocation" => result.program_location = map.next_value()?,
+                _ => {
+                    if let Some(id) = realm_id(&key) {
+                        if result.realms.contains_key(&id) {
+                            return Err(de::Error::custom(format!(
+                                "{} repeats realm {:x}",
+                                key, id
+                            )));
+                        }
+                        result.realms.insert(id, map.next_value()?);
+                    } else {
+                        map.next_value::<de::IgnoredAny>()?;
+                        result.unknown_fields.push(key);
+                    }
+                    continue;
+                }
+            }
+            found.insert(key);
+        }
+        result.missing_fields = RBSAVE_FIELDS
+            .iter()
+            .filter(|field| !found.contains(**field))
+            .cloned()
+            .collect();
+        Ok(result)
+    }
+}
+
+impl ser::Serialize for RBSave {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("allowPageInstruction", &self.allow_page_instruction)?;
+        map.serialize_entry("cameraBottom", &self.camera_bottom)?;
+        map.serialize_entry("compareRegister", &self.compare_register)?;
+        map.serialize_entry("dataRegister", &self.data_register)?;
+        map.serialize_entry("pageRegister", &self.page_register)?;
+        map.serialize_entry("jumps", &self.jumps)?;
+        map.serialize_entry("playerLocation", &self.player_location)?;
+        map.serialize_entry("playerPage", &self.player_page)?;
+        map.serialize_entry("playerX", &self.player_x)?;
+        map.serialize_entry("playerY", &self.player_y)?;
+        map.serialize_entry("programLine", &self.program_line)?;
+        map.serialize_entry("programLocation", &self.program_location)?;
+        for (id, realm) in &self.realms {
+            map.serialize_entry(&format!("realm{:x}", id), realm)?;
+        }
+        map.end()
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -498,6 +1490,7 @@ pub struct RBSave {
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::collections::HashMap;
 
     #[test]
     fn test_simple_map() {
@@ -594,6 +1587,344 @@ mod tests {
         assert_eq!(Ok(json!({"a": [{"a": ""}, {"a": ""}]})), from_bytes(&data));
     }
 
+    #[derive(Deserialize, PartialEq, Debug)]
+    enum TestEnum {
+        Unit,
+        Value(u8),
+        Pair(i32, i32),
+        Named { a: String },
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct TestTypes {
+        f: f32,
+        c: char,
+        o: Option<u8>,
+        missing: Option<u8>,
+        u: (),
+        e: TestEnum,
+        n: TestEnum,
+    }
+
+    #[test]
+    fn other_types() {
+        let data = [
+            1u8, 1, 0, 0, 0, b'f', 3, 0, 0, 0, b'1', b'.', b'5', // f
+            1, 1, 0, 0, 0, b'c', 1, 0, 0, 0, b'x', // c
+            1, 1, 0, 0, 0, b'o', 1, 0, 0, 0, b'7', // o
+            1, 1, 0, 0, 0, b'u', 0, 0, 0, 0, // u
+            1, 1, 0, 0, 0, b'e', 4, 0, 0, 0, b'U', b'n', b'i', b't', // e
+            2, 1, 0, 0, 0, b'n', 1, 5, 0, 0, 0, b'V', b'a', b'l', b'u', b'e', 1, 0, 0, 0, b'3',
+            b'c', // n
+            b'c',
+        ];
+        assert_eq!(
+            Ok(TestTypes {
+                f: 1.5,
+                c: 'x',
+                o: Some(7),
+                missing: None,
+                u: (),
+                e: TestEnum::Unit,
+                n: TestEnum::Value(3),
+            }),
+            from_bytes(&data)
+        );
+
+        let data = [
+            2u8, 4, 0, 0, 0, b'P', b'a', b'i', b'r', 3, 2, 0, 0, 0, 1, 0, 0, 0, b'1', 2, 0, 0, 0,
+            b'-', b'2', b'c', b'c',
+        ];
+        assert_eq!(Ok(TestEnum::Pair(1, -2)), from_bytes(&data));
+        let data = [
+            2u8, 5, 0, 0, 0, b'N', b'a', b'm', b'e', b'd', 1, 1, 0, 0, 0, b'a', 1, 0, 0, 0, b'z',
+            b'c', b'c',
+        ];
+        assert_eq!(Ok(TestEnum::Named { a: "z".to_owned() }), from_bytes(&data));
+
+        // bad values are errors, not panics
+        let data = [1u8, 1, 0, 0, 0, b'c', 2, 0, 0, 0, b'x', b'y', b'c'];
+        assert!(from_bytes::<TestTypes>(&data).is_err());
+        let data = [
+            2u8, 7, 0, 0, 0, b'U', b'n', b'k', b'n', b'o', b'w', b'n', b'c', b'c',
+        ];
+        assert!(from_bytes::<TestEnum>(&data).is_err());
+        assert!(from_bytes::<TestEnum>(&[9u8]).is_err());
+    }
+
+    #[test]
+    fn error_location() {
+        let data = [1u8, 1, 0, 0, 0, b'a', 1, 0, 0];
+        let err = from_bytes::<TestStructI32>(&data).err().unwrap();
+        assert_eq!(err.kind, ErrorKind::Eof);
+        assert_eq!(err.offset, Some(6));
+        assert_eq!(err.path, "a");
+
+        let data = [1u8, 1, 0, 0, 0, b'a', 1, 0, 0, 0, b'x', b'c'];
+        let err = from_bytes::<TestStructI32>(&data).err().unwrap();
+        assert_eq!((err.kind, err.offset), (ErrorKind::Syntax, Some(6)));
+
+        let data = [
+            2u8, 1, 0, 0, 0, b'a', 3, 2, 0, 0, 0, 1, 1, 0, 0, 0, b'a', 0, 0, 0, 0, b'c', 1, 1, 0,
+            0, 0, b'a', 1, 0, 0, 0, 0xff, b'c', b'c', b'c',
+        ];
+        let err = from_bytes::<serde_json::Value>(&data).err().unwrap();
+        assert_eq!(err.offset, Some(32));
+        assert_eq!(err.path, "a[1].a");
+        assert_eq!(
+            err.to_string(),
+            "Bad string at offset 32 in a[1].a".to_owned()
+        );
+
+        let err = from_bytes::<serde_json::Value>(&[b'c', b'c'])
+            .err()
+            .unwrap();
+        assert_eq!(
+            (err.kind, err.offset, err.path),
+            (ErrorKind::TrailingCharacters, Some(1), String::new())
+        );
+
+        let data = [
+            2u8, 1, 0, 0, 0, b'm', 1, 2, 0, 0, 0, b'1', b'2', 1, 0, 0, 0, b'7', b'c', b'c',
+        ];
+        let map: HashMap<String, HashMap<u16, u8>> = from_bytes(&data).unwrap();
+        assert_eq!(map["m"][&12], 7);
+
+        // errors from visitor are placed where reading stopped
+        let data = [1u8, 1, 0, 0, 0, b'b', 1, 0, 0, 0, b'5', b'c'];
+        let err = from_bytes::<TestStructI32>(&data).err().unwrap();
+        assert_eq!(err.offset, Some(12));
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct TestRoundTrip {
+        a: i32,
+        s: String,
+        o: Option<u8>,
+        list: Vec<TestRoundTripItem>,
+        empty: Vec<TestRoundTripItem>,
+        e: TestRoundTripEnum,
+        v: TestRoundTripEnum,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct TestRoundTripItem {
+        x: u16,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum TestRoundTripEnum {
+        Unit,
+        Value(u8),
+    }
+
+    #[test]
+    fn write() {
+        let data = [
+            1u8, 1, 0, 0, 0, b'a', 1, 0, 0, 0, b'b', 1u8, 2, 0, 0, 0, b'a', b'b', 1, 0, 0, 0, b'f',
+            b'c',
+        ];
+        let value: serde_json::Value = from_bytes(&data).unwrap();
+        assert_eq!(to_bytes(&value), Ok(data.to_vec()));
+
+        let data = [
+            2u8, 1, 0, 0, 0, b'a', 3, 2, 0, 0, 0, 3, 1, 0, 0, 0, 3, 0, 0, 0, 0, b'c', b'c', b'c',
+            b'c', b'c',
+        ];
+        let value: serde_json::Value = from_bytes(&data).unwrap();
+        let packed = to_bytes(&value).unwrap();
+        assert_eq!(from_bytes::<serde_json::Value>(&packed), Ok(value));
+
+        let value = TestRoundTrip {
+            a: -5,
+            s: "text".to_owned(),
+            o: None,
+            list: vec![TestRoundTripItem { x: 1 }, TestRoundTripItem { x: 300 }],
+            empty: vec![],
+            e: TestRoundTripEnum::Unit,
+            v: TestRoundTripEnum::Value(7),
+        };
+        let packed = to_bytes(&value).unwrap();
+        assert_eq!(from_bytes(&packed), Ok(value));
+
+        assert!(to_bytes(&5).is_err());
+        assert!(to_bytes(&serde_json::json!({"a": [1, 2]})).is_err());
+        assert_eq!(
+            to_bytes(&serde_json::json!({"a": 12})),
+            Ok(vec![1u8, 1, 0, 0, 0, b'a', 2, 0, 0, 0, b'1', b'2', b'c'])
+        );
+    }
+
+    #[test]
+    fn rb_save() {
+        let data = to_bytes(&serde_json::json!({
+            "allowPageInstruction": "1",
+            "jumps": [{"code": "4112", "realm": "66", "x": "1", "y": "2"}],
+            "playerPage": "66",
+            "realm2": {"257": "64"},
+            "realm42": {},
+            "realmff": {"1": "1"},
+            "somethingNew": {"a": "b"},
+        }))
+        .unwrap();
+        let save: RBSave = from_bytes(&data).unwrap();
+        assert_eq!(save.allow_page_instruction, 1);
+        assert_eq!(save.player_page, 0x42);
+        assert_eq!(save.jumps[0].code, 0x1010);
+        assert_eq!(
+            save.realms.keys().cloned().collect::<Vec<_>>(),
+            vec![2, 0x42, 0xff]
+        );
+        assert_eq!(save.realms[&2][&257], 64);
+        assert_eq!(save.unknown_fields, vec!["somethingNew".to_owned()]);
+        assert!(save.missing_fields.contains(&"cameraBottom"));
+        assert!(!save.missing_fields.contains(&"playerPage"));
+
+        let packed = to_bytes(&save).unwrap();
+        let repacked: RBSave = from_bytes(&packed).unwrap();
+        assert_eq!(repacked.realms, save.realms);
+        assert_eq!(repacked.jumps, save.jumps);
+        assert!(repacked.missing_fields.is_empty());
+
+        let data = to_bytes(&serde_json::json!({"realm2": {}, "realm02": {}})).unwrap();
+        assert!(from_bytes::<RBSave>(&data).is_err());
+    }
+
+    fn sample_save() -> Vec<u8> {
+        to_bytes(&json!({
+            "allowPageInstruction": "1",
+            "cameraBottom": "-3",
+            "jumps": [{"achievement": "x", "code": "4112", "realm": "66", "x": "1", "y": "2"}],
+            "playerPage": "66",
+            "realm2": {"257": "64", "1": "2"},
+            "realm42": {},
+            "nested": [[{"a": "b"}], {}],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn truncated_input() {
+        let data = sample_save();
+        assert!(from_bytes::<RBSave>(&data).is_ok());
+        for len in 0..data.len() {
+            assert!(from_bytes::<serde_json::Value>(&data[..len]).is_err());
+            assert!(from_bytes::<RBSave>(&data[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn random_input() {
+        // xorshift, fixed seed to keep failures reproducible
+        let mut state = 0x2545_f491u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let sample = sample_save();
+        for _ in 0..2000 {
+            let mut data = sample.clone();
+            for _ in 0..1 + next() % 4 {
+                let i = next() as usize % data.len();
+                data[i] = match next() % 4 {
+                    0 => next() as u8,
+                    1 => 0xff,
+                    2 => b'c',
+                    _ => (next() % 4) as u8,
+                };
+            }
+            let _ = from_bytes::<serde_json::Value>(&data);
+            let _ = from_bytes::<RBSave>(&data);
+
+            let len = next() as usize % 64;
+            let data: Vec<u8> = (0..len)
+                .map(|_| [0, 1, 2, 3, b'c', 0xff][next() as usize % 6])
+                .collect();
+            let _ = from_bytes::<serde_json::Value>(&data);
+            let _ = from_bytes::<RBSave>(&data);
+        }
+    }
+
+    #[test]
+    fn limits() {
+        // deep nesting fails before running out of stack
+        let mut data = Vec::new();
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[2u8, 1, 0, 0, 0, b'a']);
+        }
+        let err = from_bytes::<serde_json::Value>(&data).err().unwrap();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded("nested values"));
+
+        // list length larger than input
+        let data = [2u8, 1, 0, 0, 0, b'a', 3, 0xff, 0xff, 0xff, 0xff, b'c', b'c'];
+        let err = from_bytes::<serde_json::Value>(&data).err().unwrap();
+        assert_eq!((err.kind, err.offset), (ErrorKind::Eof, Some(7)));
+
+        // string length larger than input
+        let data = [1u8, 1, 0, 0, 0, b'a', 0xff, 0xff, 0xff, 0xff, b'c'];
+        assert!(from_bytes::<serde_json::Value>(&data).is_err());
+
+        assert!(Limits::default().max_values > 256 * 65536);
+
+        let limits = Limits {
+            max_depth: 2,
+            max_list_len: 1,
+            max_values: 3,
+        };
+        let data = to_bytes(&json!({"a": [{}, {}]})).unwrap();
+        let err = from_bytes_with_limits::<serde_json::Value>(&data, limits)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded("list elements"));
+        let data = to_bytes(&json!({"a": "1", "b": "2", "c": "3", "d": "4"})).unwrap();
+        let err = from_bytes_with_limits::<serde_json::Value>(&data, limits)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded("values"));
+        let data = to_bytes(&json!({"a": {"b": {}}})).unwrap();
+        let err = from_bytes_with_limits::<serde_json::Value>(&data, limits)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind, ErrorKind::LimitExceeded("nested values"));
+    }
+
+    #[test]
+    fn schema_dump() {
+        let data = [
+            2u8, 1, 0, 0, 0, b'a', 3, 3, 0, 0, 0, 1, 1, 0, 0, 0, b'x', 0, 0, 0, 0, b'c', 1, 1, 0,
+            0, 0, b'x', 1, 0, 0, 0, b'1', b'c', 3, 0, 0, 0, 0, b'c', b'c', b'c',
+        ];
+        let node = schema(&data).unwrap();
+        assert_eq!(node.offset, 0);
+        let entries = match &node.kind {
+            SchemaKind::Map(entries) => entries,
+            _ => panic!("expected map"),
+        };
+        assert_eq!(entries[0].0, "a");
+        assert_eq!(entries[0].1.offset, 6);
+
+        let mut out = Vec::new();
+        node.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "       0  map, 1 entries
+       6    a: list, 3 elements
+      11      [0]: map, 1 entries
+      17        x: string \"\"
+      22      [1]: same as [0]
+      34      [2]: list, 0 elements
+"
+        );
+
+        let err = schema(&data[..20]).err().unwrap();
+        assert_eq!((err.kind, err.path), (ErrorKind::Eof, "a[0].x".to_owned()));
+        let data = sample_save();
+        assert!(schema(&data).is_ok());
+    }
+
     #[test]
     fn nested_array() {
         // two empty lists
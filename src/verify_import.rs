@@ -0,0 +1,254 @@
+use std::io::{Result, Write};
+
+use crate::gameplay::{GamePlayState, PlayerPos, Trigger};
+use tgame::vecmath::V2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct CellMismatch {
+    pub x: u8,
+    pub y: u8,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+pub enum JumpMismatch {
+    Missing(Trigger),
+    Extra(Trigger),
+    Changed { expected: Trigger, actual: Trigger },
+}
+
+impl JumpMismatch {
+    fn pos(&self) -> V2 {
+        match self {
+            JumpMismatch::Missing(t) | JumpMismatch::Extra(t) => t.pos(),
+            JumpMismatch::Changed { expected, .. } => expected.pos(),
+        }
+    }
+}
+
+pub enum PageCheck {
+    MissingPage,
+    ExtraPage,
+    Compared {
+        cells: Vec<CellMismatch>,
+        jumps: Vec<JumpMismatch>,
+    },
+}
+
+/// Differences between level loaded from reference pages and the same level imported from
+/// another format.
+pub struct ImportReport {
+    pub pages: Vec<(u8, PageCheck)>,
+    /// Player page and position, expected and actual.
+    pub player: Option<((u8, PlayerPos), (u8, PlayerPos))>,
+}
+
+fn sorted_triggers<'a, I: Iterator<Item = &'a Trigger>>(triggers: I) -> Vec<&'a Trigger> {
+    let mut result: Vec<&Trigger> = triggers.collect();
+    result.sort_by_key(|t| (t.pos().y, t.pos().x));
+    result
+}
+
+impl ImportReport {
+    pub fn new(expected: &GamePlayState, actual: &GamePlayState) -> ImportReport {
+        let mut ids: Vec<u8> = expected
+            .pages
+            .keys()
+            .chain(actual.pages.keys())
+            .cloned()
+            .collect();
+        ids.sort();
+        ids.dedup();
+        let pages = ids
+            .into_iter()
+            .map(|id| {
+                let check = match (expected.pages.get(&id), actual.pages.get(&id)) {
+                    (Some(e), Some(a)) => {
+                        let cells = e
+                            .memory
+                            .iter()
+                            .filter(|((x, y), v)| a.memory[(*x, *y)] != **v)
+                            .map(|((x, y), v)| CellMismatch {
+                                x,
+                                y,
+                                expected: *v,
+                                actual: a.memory[(x, y)],
+                            })
+                            .collect();
+                        let mut jumps = Vec::new();
+                        for t in sorted_triggers(e.triggers.values()) {
+                            let other = a.triggers.values().find(|other| other.pos() == t.pos());
+                            match other {
+                                None => jumps.push(JumpMismatch::Missing(t.clone())),
+                                Some(other) if !t.same_effect(other) => {
+                                    jumps.push(JumpMismatch::Changed {
+                                        expected: t.clone(),
+                                        actual: other.clone(),
+                                    })
+                                }
+                                _ => {}
+                            }
+                        }
+                        for t in sorted_triggers(a.triggers.values()) {
+                            if !e.triggers.values().any(|other| other.pos() == t.pos()) {
+                                jumps.push(JumpMismatch::Extra(t.clone()));
+                            }
+                        }
+                        jumps.sort_by_key(|j| (j.pos().y, j.pos().x));
+                        PageCheck::Compared { cells, jumps }
+                    }
+                    (Some(_), None) => PageCheck::MissingPage,
+                    _ => PageCheck::ExtraPage,
+                };
+                (id, check)
+            })
+            .collect();
+        let expected_player = (expected.player_page, expected.player);
+        let actual_player = (actual.player_page, actual.player);
+        ImportReport {
+            pages,
+            player: if expected_player != actual_player {
+                Some((expected_player, actual_player))
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.player.is_none()
+            && self.pages.iter().all(|(_, check)| match check {
+                PageCheck::Compared { cells, jumps } => cells.is_empty() && jumps.is_empty(),
+                _ => false,
+            })
+    }
+
+    pub fn write_text(&self, out: &mut Write) -> Result<()> {
+        let player_text = |(page, pos): (u8, PlayerPos)| match pos {
+            PlayerPos::Pos(p) => format!("page {:02x} at {},{}", page, p.x, p.y),
+            PlayerPos::Register(r) => format!("page {:02x} in register {}", page, r),
+        };
+        if let Some((expected, actual)) = self.player {
+            writeln!(
+                out,
+                "player: expected {}, got {}",
+                player_text(expected),
+                player_text(actual)
+            )?;
+        }
+        let mut problems = 0;
+        for (id, check) in &self.pages {
+            match check {
+                PageCheck::MissingPage => {
+                    problems += 1;
+                    writeln!(out, "page {:02x}: missing from import", id)?;
+                }
+                PageCheck::ExtraPage => {
+                    problems += 1;
+                    writeln!(out, "page {:02x}: not in reference level", id)?;
+                }
+                PageCheck::Compared { cells, jumps } => {
+                    problems += cells.len() + jumps.len();
+                    writeln!(
+                        out,
+                        "page {:02x}: {} cells differ, {} jump mismatches",
+                        id,
+                        cells.len(),
+                        jumps.len()
+                    )?;
+                    for cell in cells {
+                        writeln!(
+                            out,
+                            "  cell {:3},{:3}: expected {:02x} got {:02x}",
+                            cell.x, cell.y, cell.expected, cell.actual
+                        )?;
+                    }
+                    for jump in jumps {
+                        let p = jump.pos();
+                        match jump {
+                            JumpMismatch::Missing(t) => writeln!(
+                                out,
+                                "  missing jump at {},{}: {}",
+                                p.x,
+                                p.y,
+                                t.effect_text()
+                            )?,
+                            JumpMismatch::Extra(t) => writeln!(
+                                out,
+                                "  extra jump at {},{}: {}",
+                                p.x,
+                                p.y,
+                                t.effect_text()
+                            )?,
+                            JumpMismatch::Changed { expected, actual } => writeln!(
+                                out,
+                                "  jump at {},{}: expected {} got {}",
+                                p.x,
+                                p.y,
+                                expected.effect_text(),
+                                actual.effect_text()
+                            )?,
+                        }
+                    }
+                }
+            }
+        }
+        if self.is_ok() {
+            writeln!(out, "import matches")
+        } else {
+            writeln!(out, "{} mismatches", problems + self.player.iter().count())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytegrid::ByteGrid;
+    use crate::gameplay::{PageState, DEFAULT_PAGE};
+
+    fn trigger(text: &str) -> Trigger {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn mismatches() {
+        let level = || {
+            let mut game = GamePlayState::from_grid(ByteGrid::from_raw_str(b"ab\n @ x"));
+            game.pages.insert(0x02, PageState::new());
+            game
+        };
+        let mut reference = level();
+        let mut imported = level();
+        assert!(ImportReport::new(&reference, &imported).is_ok());
+
+        let page = reference.pages.get_mut(&DEFAULT_PAGE).unwrap();
+        page.triggers
+            .insert(0x0100, trigger("pos: {x: 1, y: 0}\neffect: EndOfLevel"));
+        page.triggers
+            .insert(0x0000, trigger("pos: {x: 0, y: 0}\neffect: {SetPC: 4660}"));
+        let page = imported.pages.get_mut(&DEFAULT_PAGE).unwrap();
+        page.memory[(0, 1)] = b'c';
+        page.triggers
+            .insert(0x0100, trigger("pos: {x: 1, y: 0}\neffect: {Message: WIN}"));
+        page.triggers
+            .insert(0x0200, trigger("pos: {x: 2, y: 0}\neffect: {SetPC: 4660}"));
+        imported.pages.remove(&0x02);
+        imported.pages.insert(0x03, PageState::new());
+
+        let report = ImportReport::new(&reference, &imported);
+        assert!(!report.is_ok());
+        let mut text = Vec::new();
+        report.write_text(&mut text).unwrap();
+        assert_eq!(
+            String::from_utf8(text).unwrap(),
+            "page 02: missing from import\n\
+             page 03: not in reference level\n\
+             page 42: 1 cells differ, 2 jump mismatches\n  \
+             cell   0,  1: expected 20 got 63\n  \
+             missing jump at 0,0: SetPC(1234)\n  \
+             extra jump at 2,0: SetPC(1234)\n\
+             5 mismatches\n"
+        );
+    }
+}